@@ -0,0 +1,480 @@
+#![deny(missing_docs)]
+
+//! Command-line interface of the Sudoku library
+extern crate sudoku;
+
+use std::env;
+use std::fs::{self,File};
+use std::io::{self,BufRead,BufReader,Write};
+use std::process;
+use std::rc::Rc;
+
+use sudoku::puzzle_file::Entry;
+use sudoku::constraint::Diagonals;
+use sudoku::{Budget,DancingLinksSolver,Difficulty,Gameboard,Generator,GeneratorOptions,
+    PuzzleReader,PuzzleWriter,Regions,Shape,Solver,SudokuSolver};
+
+const USAGE : &str = "\
+usage: sudoku <command> [options] [FILE]
+
+Puzzles are read from FILE (or stdin if FILE is missing or `-`), one
+line per puzzle: 81 characters for 9x9 boards, n*n for other sizes with
+letters for digits above 9. Results are written to stdout.
+
+commands:
+  solve       print the solution of every puzzle
+  generate    print new puzzles
+  rate        print the difficulty of every puzzle
+  validate    check that every puzzle has exactly one solution
+  count       print the number of solutions of every puzzle
+  convert     print every puzzle in another format
+
+options:
+  --solver NAME        solver backend: propagation (default) or dlx
+  --diagonals          both main diagonals hold every digit too (Sudoku X)
+  --regions FILE       Jigsaw: the regions of the layout in FILE replace the boxes
+  -n, --count N        generate: number of puzzles (default 1)
+  --seed SEED          generate: seed of the puzzle sequence
+  --size SIZE          generate: board size such as 6 or box size such as 3x2 (default 9,
+                       or the size of the --regions layout)
+  --difficulty LEVEL   generate: easy, medium, hard, expert, evil or LOW..HIGH
+  --attempts N         generate: attempts per puzzle for --difficulty (default 100)
+  --symmetry NAME      generate: none, rot180, rot90, horizontal, vertical, diagonal
+  --min-givens N       generate: never dig below N givens
+  --max-givens N       generate: at most N givens
+  --minimal            generate: remove every unnecessary given
+  --max N              count: stop counting at N solutions, 0 for no limit (default 1000)
+  --format NAME        convert: line (default) or grid
+  --blank CHAR         convert: character of empty cells (default `.`)";
+
+/// Options which take a value
+const VALUED : [&str;14] = [
+    "--solver","--regions","-n","--count","--seed","--size","--difficulty","--attempts","--symmetry",
+    "--min-givens","--max-givens","--max","--format","--blank",
+];
+
+/// Error which stops a command
+#[derive(Debug,PartialEq)]
+enum CliError{
+    /// Bad command line, reported together with the usage
+    Usage(String),
+    /// Failure while running, e.g. an unreadable file
+    Failed(String),
+}
+
+impl From<String> for CliError{
+    fn from(message: String) -> CliError{
+        CliError::Usage(message)
+    }
+}
+
+impl From<&str> for CliError{
+    fn from(message: &str) -> CliError{
+        CliError::Usage(message.to_string())
+    }
+}
+
+/// Parsed command line
+struct Args{
+    /// Subcommand
+    command : String,
+    /// Input file
+    input : Option<String>,
+    /// `(name, value)` of options
+    options : Vec<(String,String)>,
+    /// Layout of `--regions`
+    regions : Option<Regions>,
+}
+
+impl Args{
+    /// Parse the arguments after the program name
+    fn parse(args: &[String]) -> Result<Args,CliError>{
+        let mut iter = args.iter();
+        let command = iter.next().ok_or("missing command")?.clone();
+        let mut input = None;
+        let mut options = vec![];
+
+        while let Some(arg) = iter.next(){
+            if VALUED.contains(&arg.as_str()){
+                let value = iter.next().ok_or_else(|| format!("{} needs a value",arg))?;
+                options.push((arg.clone(),value.clone()));
+            }else if ["--minimal","--diagonals","-h","--help"].contains(&arg.as_str()){
+                options.push((arg.clone(),String::new()));
+            }else if arg.starts_with('-') && arg != "-"{
+                return Err(format!("unknown option {}",arg).into());
+            }else if input.is_none(){
+                input = Some(arg.clone());
+            }else{
+                return Err(format!("unexpected argument {}",arg).into());
+            }
+        }
+
+        let mut args = Args{command,input,options,regions : None};
+        if let Some(path) = args.get(&["--regions"]){
+            let failed = |e: &dyn std::fmt::Display| CliError::Failed(format!("{}: {}",path,e));
+            let layout = fs::read_to_string(path).map_err(|e| failed(&e))?;
+            args.regions = Some(layout.parse().map_err(|e| failed(&e))?);
+        }
+
+        Ok(args)
+    }
+
+    /// Value of an option (the last one wins)
+    fn get(&self,names: &[&str]) -> Option<&str>{
+        self.options.iter()
+            .rev()
+            .find(|(n,_)| names.contains(&n.as_str()))
+            .map(|(_,v)| v.as_str())
+    }
+
+    /// Parsed value of an option
+    fn parse_value<T: std::str::FromStr>(&self,names: &[&str],default: T) -> Result<T,String>
+        where T::Err: std::fmt::Display
+    {
+        match self.get(names){
+            Some(v) => v.parse().map_err(|e| format!("{} {:?}: {}",names[0],v,e)),
+            None => Ok(default),
+        }
+    }
+
+    /// Solver backend
+    fn solver(&self) -> Result<Box<dyn SudokuSolver>,String>{
+        match self.get(&["--solver"]).unwrap_or("propagation"){
+            "propagation" => Ok(Box::new(Solver::new())),
+            "dlx" => Ok(Box::new(DancingLinksSolver::new())),
+            name => Err(format!("unknown solver {:?}",name)),
+        }
+    }
+
+    /// Add the rules of the options to a gameboard
+    fn apply_rules(&self,gb: &mut Gameboard) -> Result<(),String>{
+        if self.get(&["--diagonals"]).is_some(){
+            gb.add_rule(Rc::new(Diagonals));
+        }
+        if let Some(ref regions) = self.regions{
+            if regions.size() != gb.size(){
+                return Err(format!("the regions are for {0}x{0} boards",regions.size()));
+            }
+            gb.set_regions(regions.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Open the input
+    fn reader(&self) -> Result<PuzzleReader<Box<dyn BufRead>>,CliError>{
+        let reader : Box<dyn BufRead> = match self.input.as_deref(){
+            None | Some("-") => Box::new(BufReader::new(io::stdin())),
+            Some(path) => Box::new(BufReader::new(
+                File::open(path).map_err(|e| CliError::Failed(format!("{}: {}",path,e)))?)),
+        };
+
+        Ok(PuzzleReader::new(reader))
+    }
+}
+
+fn main(){
+    let args = env::args().skip(1).collect::<Vec<String>>();
+
+    let code = match Args::parse(&args).and_then(|args| run(&args)){
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(CliError::Usage(message)) => {
+            eprintln!("sudoku: {}",message);
+            eprintln!("{}",USAGE);
+            2
+        },
+        Err(CliError::Failed(message)) => {
+            eprintln!("sudoku: {}",message);
+            1
+        },
+    };
+
+    process::exit(code);
+}
+
+/// Run a command. Returns `false` if some puzzle failed.
+fn run(args: &Args) -> Result<bool,CliError>{
+    if args.get(&["-h","--help"]).is_some(){
+        println!("{}",USAGE);
+        return Ok(true);
+    }
+
+    let stdout = io::stdout();
+
+    match args.command.as_str(){
+        "solve" => {
+            let mut solver = args.solver()?;
+            let mut writer = PuzzleWriter::new(stdout.lock());
+            each_entry(args,|entry|{
+                match solution(&entry.gameboard,&mut *solver){
+                    Some(gb) => {
+                        writer.write(&gb,&entry.comment)?;
+                        Ok(true)
+                    },
+                    None => {
+                        eprintln!("line {}: no solution",entry.line);
+                        Ok(false)
+                    },
+                }
+            })
+        },
+        "rate" => {
+            let mut writer = PuzzleWriter::new(stdout.lock());
+            each_entry(args,|entry|{
+                let rating = entry.gameboard.rate();
+                let hardest = rating.hardest.map_or("",|t| t.name());
+                let comment = format!("{} {} {}",rating,hardest,entry.comment);
+                writer.write(&entry.gameboard,comment.trim())?;
+                Ok(true)
+            })
+        },
+        "validate" => {
+            let mut solver = args.solver()?;
+            let mut writer = PuzzleWriter::new(stdout.lock());
+            each_entry(args,|entry|{
+                let gb = &entry.gameboard;
+                let status = if gb.invalid(){
+                    "invalid"
+                }else{
+                    match solver.make_answer_list(gb,2).len(){
+                        0 => "no solution",
+                        1 => "ok",
+                        _ => "multiple solutions",
+                    }
+                };
+                writer.write(gb,status)?;
+                Ok(status == "ok")
+            })
+        },
+        "count" => {
+            let mut solver = args.solver()?;
+            let max = args.parse_value(&["--max"],1000usize)?;
+            let mut writer = PuzzleWriter::new(stdout.lock());
+            each_entry(args,|entry|{
+                // count without keeping the answers
+                let mut n = 0;
+                solver.for_each_answer(&entry.gameboard,&mut |_|{
+                    n += 1;
+                    max == 0 || n < max
+                });
+                let count = if max > 0 && n == max {format!(">={}",n)} else {n.to_string()};
+                writer.write(&entry.gameboard,&count)?;
+                Ok(true)
+            })
+        },
+        "convert" => {
+            let blank = args.parse_value(&["--blank"],'.')?;
+            let grid = match args.get(&["--format"]).unwrap_or("line"){
+                "line" => false,
+                "grid" => true,
+                name => return Err(format!("unknown format {:?}",name).into()),
+            };
+            let mut out = stdout.lock();
+            each_entry(args,|entry|{
+                let gb = &entry.gameboard;
+                if grid{
+                    write!(out,"{}",format_grid(gb,blank))?;
+                }else{
+                    writeln!(out,"{}",gb.to_string().replace('.',&blank.to_string()))?;
+                }
+                Ok(true)
+            })
+        },
+        "generate" => generate(args,&mut PuzzleWriter::new(stdout.lock())),
+        command => Err(format!("unknown command {:?}",command).into()),
+    }
+}
+
+/// Run `f` for every puzzle of the input
+fn each_entry<F>(args: &Args,mut f: F) -> Result<bool,CliError>
+    where F: FnMut(Entry) -> io::Result<bool>
+{
+    let mut success = true;
+
+    for entry in args.reader()?{
+        match entry{
+            Ok(mut entry) => {
+                if let Err(e) = args.apply_rules(&mut entry.gameboard){
+                    eprintln!("line {}: {}",entry.line,e);
+                    success = false;
+                    continue;
+                }
+                success &= f(entry).map_err(|e| CliError::Failed(e.to_string()))?;
+            },
+            Err(e) => {
+                eprintln!("{}",e);
+                success = false;
+            },
+        }
+    }
+
+    Ok(success)
+}
+
+/// Board filled with the first answer of `solver`, `None` if the puzzle
+/// breaks a rule or has no answer
+fn solution(gb: &Gameboard,solver: &mut dyn SudokuSolver) -> Option<Gameboard>{
+    if gb.invalid(){
+        return None;
+    }
+    let answer = solver.make_answer_list(gb,1).pop()?;
+    let mut solved = gb.clone();
+    for (i,j) in gb.shape().cells(){
+        solved.set((i,j),answer[i][j]);
+    }
+
+    Some(solved)
+}
+
+/// `generate` command
+fn generate<W: Write>(args: &Args,writer: &mut PuzzleWriter<W>) -> Result<bool,CliError>{
+    let size = args.regions.as_ref().and_then(|regions| Shape::from_size(regions.size()));
+    let shape = args.parse_value(&["--size"],size.unwrap_or(Shape::CLASSIC))?;
+    let default = GeneratorOptions::for_shape(shape);
+    let options = GeneratorOptions{
+        min_givens : args.parse_value(&["--min-givens"],default.min_givens)?,
+        max_givens : args.parse_value(&["--max-givens"],default.max_givens)?,
+        symmetry : args.parse_value(&["--symmetry"],default.symmetry)?,
+        minimal : args.get(&["--minimal"]).is_some(),
+    };
+    let count = args.parse_value(&["-n","--count"],1usize)?;
+    let budget = Budget{
+        max_attempts : args.parse_value(&["--attempts"],Budget::default().max_attempts)?,
+        max_duration : None,
+    };
+    let range = match args.get(&["--difficulty"]){
+        Some(v) => Some(parse_range(v)?),
+        None => None,
+    };
+
+    let mut generator = match args.get(&["--seed"]){
+        Some(_) => Generator::from_seed(args.parse_value(&["--seed"],0u64)?),
+        None => Generator::new(),
+    };
+    generator.set_options(options).map_err(|e| e.to_string())?;
+
+    let mut success = true;
+    for _ in 0..count{
+        let mut gb = Gameboard::with_shape(shape);
+        args.apply_rules(&mut gb)?;
+        let result = match range{
+            Some(ref range) => generator.generate_with_difficulty(&mut gb,range.clone(),&budget),
+            None => generator.generate(&mut gb),
+        };
+        let seed = match result{
+            Ok(seed) => seed,
+            Err(e) => {
+                eprintln!("{}",e);
+                success = false;
+                continue;
+            },
+        };
+
+        writer.write(&gb,&format!("seed={} {}",seed,gb.rate()))
+            .map_err(|e| CliError::Failed(e.to_string()))?;
+    }
+
+    Ok(success)
+}
+
+/// Parse `LEVEL` or `LOW..HIGH`
+fn parse_range(v: &str) -> Result<std::ops::RangeInclusive<Difficulty>,String>{
+    match v.find(".."){
+        Some(k) => Ok(v[..k].parse()?..=v[k+2..].parse()?),
+        None => {
+            let d = v.parse()?;
+            Ok(d..=d)
+        },
+    }
+}
+
+/// Format a gameboard as a grid with section separators, a line per row
+fn format_grid(gb: &Gameboard,blank: char) -> String{
+    let (n,w,h) = (gb.size(),gb.shape().box_width,gb.shape().box_height);
+    let line = vec!["-".repeat(2*w + 1);n/w].join("+");
+    let mut s = String::new();
+
+    for i in 0..n{
+        if i > 0 && i % h == 0{
+            s.push_str(&line[1..line.len()-1]);
+            s.push('\n');
+        }
+        for j in 0..n{
+            if j > 0 && j % w == 0{
+                s.push_str("| ");
+            }
+            s.push(gb.char((i,j)).unwrap_or(blank));
+            s.push(if j + 1 < n {' '} else {'\n'});
+        }
+    }
+
+    s
+}
+
+#[cfg(test)]
+fn strings(args: &[&str]) -> Vec<String>{
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_parse_args(){
+    let args = Args::parse(&strings(&["count","--max","5","in.txt","-n","1","--count","2","--diagonals"])).unwrap();
+    assert_eq!(args.command,"count");
+    assert_eq!(args.input.as_deref(),Some("in.txt"));
+    assert_eq!(args.get(&["--max"]),Some("5"));
+    assert_eq!(args.parse_value(&["-n","--count"],1usize),Ok(2));
+    assert_eq!(args.get(&["--diagonals"]),Some(""));
+    assert!(args.get(&["--seed"]).is_none() && args.regions.is_none());
+    assert!(args.parse_value(&["--max"],false).is_err());
+
+    let args = Args::parse(&strings(&["solve","-"])).unwrap();
+    assert_eq!(args.input.as_deref(),Some("-"));
+
+    let usage = |message: &str| Some(CliError::Usage(message.to_string()));
+    assert_eq!(Args::parse(&[]).err(),usage("missing command"));
+    assert_eq!(Args::parse(&strings(&["solve","--max"])).err(),usage("--max needs a value"));
+    assert_eq!(Args::parse(&strings(&["solve","--bogus"])).err(),usage("unknown option --bogus"));
+    assert_eq!(Args::parse(&strings(&["solve","a","b"])).err(),usage("unexpected argument b"));
+    // a missing file isn't a usage error
+    assert!(matches!(Args::parse(&strings(&["solve","--regions","no/such/layout"])),Err(CliError::Failed(_))));
+}
+
+#[test]
+fn test_parse_range(){
+    assert_eq!(parse_range("easy..hard"),Ok(Difficulty::Easy..=Difficulty::Hard));
+    assert_eq!(parse_range("medium"),Ok(Difficulty::Medium..=Difficulty::Medium));
+    assert!(parse_range("easy..").is_err());
+    assert!(parse_range("impossible").is_err());
+}
+
+#[test]
+fn test_format_grid(){
+    let gb : Gameboard = "1..4.3.....2..1.".parse().unwrap();
+
+    assert_eq!(format_grid(&gb,'.'),"1 . | . 4\n. 3 | . .\n----+----\n. . | . 2\n. . | 1 .\n");
+    assert_eq!(format_grid(&gb,'0').lines().next(),Some("1 0 | 0 4"));
+}
+
+#[test]
+fn test_solution(){
+    let gb : Gameboard = "1..4.3.....2..1.".parse().unwrap();
+    let solved = solution(&gb,&mut Solver::new()).unwrap();
+    assert_eq!(solved.to_string(),"1234432131422413");
+
+    // a given breaks a rule
+    let gb : Gameboard = "11..............".parse().unwrap();
+    assert!(solution(&gb,&mut DancingLinksSolver::new()).is_none());
+    // no answer: (0,3) can only hold 4
+    let gb : Gameboard = "123....4........".parse().unwrap();
+    assert!(solution(&gb,&mut Solver::new()).is_none());
+}
+
+#[test]
+fn test_run_errors(){
+    let args = Args::parse(&strings(&["count","no/such/puzzles"])).unwrap();
+    assert!(matches!(run(&args),Err(CliError::Failed(_))));
+
+    let args = Args::parse(&strings(&["count","--solver","magic","-"])).unwrap();
+    assert_eq!(run(&args).err(),Some(CliError::Usage("unknown solver \"magic\"".to_string())));
+}
@@ -0,0 +1,227 @@
+//! Rules of a board
+//!
+//! Every rule is a `Constraint`. The classic rules are `Rows`, `Columns` and
+//! `Boxes`, which a gameboard starts with; variants add rules to this list
+//! (e.g. `Diagonals` for Sudoku X) or replace some of them.
+
+use std::rc::Rc;
+
+use crate::shape::{Grid,Pos,Shape,MAX_SIZE};
+
+/// Shared handle of a rule, as stored in a gameboard
+pub type Rule = Rc<dyn Constraint>;
+
+/// A rule every answer must follow
+pub trait Constraint{
+    /// Short name of the rule, such as `rows`
+    fn name(&self) -> &str;
+
+    /// Groups of cells which must hold different digits.
+    ///
+    /// Solvers propagate placed digits along these groups, so a rule which
+    /// can be expressed by them should return them. A group of `size` cells
+    /// must also hold every digit.
+    fn units(&self,_shape: Shape) -> Vec<Vec<Pos>>{
+        vec![]
+    }
+
+    /// Cells which break the rule. Empty cells only break rules about
+    /// completed groups. By default the repeated digits of `units`.
+    fn conflicts(&self,shape: Shape,cells: &Grid) -> Vec<Pos>{
+        self.units(shape).iter()
+            .flat_map(|unit| duplicates(cells,unit))
+            .collect()
+    }
+
+    /// if no cell breaks the rule then true
+    fn validate(&self,shape: Shape,cells: &Grid) -> bool{
+        self.conflicts(shape,cells).is_empty()
+    }
+
+    /// Remove candidates which the rule rules out beyond what `units` imply.
+    ///
+    /// `candidates` holds a digit bit mask per cell in row-major order; a
+    /// filled cell has only the bit of its digit. Returns `false` if the
+    /// rule can't be satisfied anymore.
+    fn prune(&self,_shape: Shape,_candidates: &mut [u32]) -> bool{
+        true
+    }
+}
+
+/// Every row holds different digits
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct Rows;
+
+impl Constraint for Rows{
+    fn name(&self) -> &str{
+        "rows"
+    }
+
+    fn units(&self,shape: Shape) -> Vec<Vec<Pos>>{
+        (0..shape.size()).map(|i| shape.row_cells(i)).collect()
+    }
+}
+
+/// Every column holds different digits
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct Columns;
+
+impl Constraint for Columns{
+    fn name(&self) -> &str{
+        "columns"
+    }
+
+    fn units(&self,shape: Shape) -> Vec<Vec<Pos>>{
+        (0..shape.size()).map(|j| shape.col_cells(j)).collect()
+    }
+}
+
+/// Every box (section) holds different digits
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct Boxes;
+
+impl Constraint for Boxes{
+    fn name(&self) -> &str{
+        "boxes"
+    }
+
+    fn units(&self,shape: Shape) -> Vec<Vec<Pos>>{
+        (0..shape.size()).map(|k| shape.box_cells(k)).collect()
+    }
+}
+
+/// Both main diagonals hold different digits (Sudoku X)
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct Diagonals;
+
+impl Constraint for Diagonals{
+    fn name(&self) -> &str{
+        "diagonals"
+    }
+
+    fn units(&self,shape: Shape) -> Vec<Vec<Pos>>{
+        let n = shape.size();
+
+        vec![
+            (0..n).map(|i| (i,i)).collect(),
+            (0..n).map(|i| (i,n-1-i)).collect(),
+        ]
+    }
+}
+
+/// The classic rules: rows, columns and boxes
+pub fn classic() -> Vec<Rule>{
+    vec![Rc::new(Rows),Rc::new(Columns),Rc::new(Boxes)]
+}
+
+/// The classic rules and the diagonals
+pub fn sudoku_x() -> Vec<Rule>{
+    let mut rules = classic();
+    rules.push(Rc::new(Diagonals));
+
+    rules
+}
+
+/// Rule without parameters named `name`
+pub fn from_name(name: &str) -> Option<Rule>{
+    match name{
+        "rows" => Some(Rc::new(Rows)),
+        "columns" => Some(Rc::new(Columns)),
+        "boxes" => Some(Rc::new(Boxes)),
+        "diagonals" => Some(Rc::new(Diagonals)),
+        _ => None,
+    }
+}
+
+/// All groups of cells which must hold different digits under `rules`
+pub fn units(rules: &[Rule],shape: Shape) -> Vec<Vec<Pos>>{
+    rules.iter().flat_map(|rule| rule.units(shape)).collect()
+}
+
+/// Cells of `unit` whose digit appears more than once in it
+pub fn duplicates(cells: &Grid,unit: &[Pos]) -> Vec<Pos>{
+    let mut counter = [0;MAX_SIZE+1];
+    for &(i,j) in unit.iter(){
+        counter[cells[i][j] as usize] += 1;
+    }
+    counter[0] = 0;
+
+    unit.iter()
+        .cloned()
+        .filter(|&(i,j)| counter[cells[i][j] as usize] > 1)
+        .collect()
+}
+
+/// Test rule: the top left cell holds an odd digit
+#[cfg(test)]
+struct OddCorner;
+
+#[cfg(test)]
+impl Constraint for OddCorner{
+    fn name(&self) -> &str{
+        "odd corner"
+    }
+
+    fn conflicts(&self,_shape: Shape,cells: &Grid) -> Vec<Pos>{
+        if cells[0][0].is_multiple_of(2) && cells[0][0] != 0 {vec![(0,0)]} else {vec![]}
+    }
+
+    fn prune(&self,_shape: Shape,candidates: &mut [u32]) -> bool{
+        // bits of the even digits
+        candidates[0] &= !0x5555_5554;
+        candidates[0] != 0
+    }
+}
+
+#[test]
+fn test_classic_units(){
+    let shape = Shape::from_size(6).unwrap();
+    let units = units(&classic(),shape);
+
+    assert_eq!(units.len(),18);
+    assert!(units.iter().all(|unit| unit.len() == 6));
+    assert_eq!(units[12],shape.box_cells(0));
+}
+
+#[test]
+fn test_custom_rule(){
+    use crate::gameboard::Gameboard;
+    use crate::dancing_links::DancingLinksSolver;
+    use crate::solver::{Solver,SudokuSolver};
+
+    let mut gb = Gameboard::new();
+    gb.add_rule(Rc::new(OddCorner));
+    gb.set((0,0),2);
+    assert!(gb.get_invalid((0,0)));
+    gb.set((0,0),0);
+    assert!(!gb.invalid());
+
+    for answer in Solver::new().make_answer_list(&gb,10){
+        assert_eq!(answer[0][0] % 2,1);
+    }
+    for answer in DancingLinksSolver::new().make_answer_list(&gb,10){
+        assert_eq!(answer[0][0] % 2,1);
+    }
+}
+
+#[test]
+fn test_diagonals(){
+    use crate::gameboard::Gameboard;
+    use crate::solver::{Solver,SudokuSolver};
+
+    let mut gb = Gameboard::new();
+    gb.set_rules(sudoku_x());
+    gb.set((0,0),5);
+    gb.set((8,8),5);
+    assert!(gb.get_invalid((0,0)) && gb.get_invalid((8,8)));
+    gb.set((8,8),0);
+    gb.set((1,7),3);
+    gb.set((7,1),3);
+    assert!(gb.get_invalid((1,7)) && gb.get_invalid((7,1)));
+    gb.set((7,1),0);
+
+    let answer = Solver::from_seed(3).make_answer_list(&gb,1).remove(0);
+    let shape = gb.shape();
+    assert!(sudoku_x().iter().all(|rule| rule.validate(shape,&answer)));
+    assert_eq!(from_name("diagonals").map(|rule| rule.units(shape)),Some(Diagonals.units(shape)));
+}
@@ -0,0 +1,364 @@
+//! Dancing Links (Algorithm X) exact cover solver
+
+use rand::prelude::*;
+use rand_chacha::ChaChaRng;
+
+use crate::gameboard::Gameboard;
+use crate::shape::Grid;
+use crate::solver::{SudokuSolver,shuffle};
+
+/// Exact cover matrix stored as Dancing Links.
+///
+/// A solution is a set of rows which covers every primary column exactly
+/// once and every secondary column at most once.
+/// Node `0` is the root and the next nodes are the column headers.
+pub struct ExactCover{
+    /// Left neighbour of every node.
+    left : Vec<usize>,
+    /// Right neighbour of every node.
+    right : Vec<usize>,
+    /// Upper neighbour of every node.
+    up : Vec<usize>,
+    /// Lower neighbour of every node.
+    down : Vec<usize>,
+    /// Column header of every node.
+    column : Vec<usize>,
+    /// Row id of every node.
+    row : Vec<usize>,
+    /// Number of nodes in every column.
+    size : Vec<usize>,
+}
+
+impl ExactCover{
+    /// Create an empty matrix with `n_columns` columns.
+    pub fn new(n_columns: usize) -> ExactCover{
+        ExactCover::with_secondary(n_columns,0)
+    }
+
+    /// Create an empty matrix with `n_columns` primary columns followed by
+    /// `n_secondary` secondary columns.
+    pub fn with_secondary(n_columns: usize,n_secondary: usize) -> ExactCover{
+        let m = n_columns + 1;
+        let n = m + n_secondary;
+
+        // secondary headers aren't linked to the root, so they are never chosen
+        ExactCover{
+            left : (0..n).map(|i| if i < m {(i + m - 1) % m} else {i}).collect(),
+            right : (0..n).map(|i| if i < m {(i + 1) % m} else {i}).collect(),
+            up : (0..n).collect(),
+            down : (0..n).collect(),
+            column : (0..n).collect(),
+            row : vec![usize::MAX;n],
+            size : vec![0;n],
+        }
+    }
+
+    /// Add a row covering `columns`. `row_id` is reported in the solutions.
+    pub fn add_row(&mut self,row_id: usize,columns: &[usize]){
+        let mut first = None;
+
+        for &c in columns{
+            let header = c + 1;
+            let node = self.left.len();
+
+            // append to the bottom of the column
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            let last = self.up[header];
+            self.down[last] = node;
+            self.up[header] = node;
+            self.column.push(header);
+            self.row.push(row_id);
+            self.size[header] += 1;
+
+            // append to the end of the row
+            match first{
+                None => {
+                    self.left.push(node);
+                    self.right.push(node);
+                    first = Some(node);
+                },
+                Some(f) => {
+                    let last = self.left[f];
+                    self.left.push(last);
+                    self.right.push(f);
+                    self.right[last] = node;
+                    self.left[f] = node;
+                },
+            }
+        }
+    }
+
+    /// Search solutions (up to n_answer solutions).
+    /// Every solution is the list of selected row ids.
+    pub fn solve(&mut self,n_answer: usize) -> Vec<Vec<usize>>{
+        let mut answers = vec![];
+
+        if n_answer > 0{
+            self.for_each_solution(&mut |rows|{
+                answers.push(rows.to_vec());
+                answers.len() < n_answer
+            });
+        }
+
+        answers
+    }
+
+    /// Call `f` with the selected row ids of every solution until it
+    /// returns `false`.
+    pub fn for_each_solution(&mut self,f: &mut dyn FnMut(&[usize]) -> bool){
+        let mut partial = vec![];
+
+        self.search(&mut partial,f);
+    }
+
+    /// Core part of Algorithm X.
+    /// Returns `false` if `f` asked to stop.
+    fn search(&mut self,partial: &mut Vec<usize>,f: &mut dyn FnMut(&[usize]) -> bool) -> bool{
+        if self.right[0] == 0{
+            return f(partial);
+        }
+
+        // choose the column with fewest rows
+        let mut c = self.right[0];
+        let mut j = self.right[c];
+        while j != 0{
+            if self.size[j] < self.size[c]{
+                c = j;
+            }
+            j = self.right[j];
+        }
+
+        if self.size[c] == 0{
+            return true;
+        }
+
+        self.cover(c);
+
+        let mut go_on = true;
+        let mut r = self.down[c];
+        while r != c && go_on{
+            partial.push(self.row[r]);
+
+            let mut j = self.right[r];
+            while j != r{
+                self.cover(self.column[j]);
+                j = self.right[j];
+            }
+
+            go_on = self.search(partial,f);
+
+            let mut j = self.left[r];
+            while j != r{
+                self.uncover(self.column[j]);
+                j = self.left[j];
+            }
+
+            partial.pop();
+            r = self.down[r];
+        }
+
+        self.uncover(c);
+
+        go_on
+    }
+
+    /// Remove column `c` and every row which intersects it.
+    fn cover(&mut self,c: usize){
+        self.right[self.left[c]] = self.right[c];
+        self.left[self.right[c]] = self.left[c];
+
+        let mut i = self.down[c];
+        while i != c{
+            let mut j = self.right[i];
+            while j != i{
+                self.down[self.up[j]] = self.down[j];
+                self.up[self.down[j]] = self.up[j];
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    /// Undo `cover(c)`.
+    fn uncover(&mut self,c: usize){
+        let mut i = self.up[c];
+        while i != c{
+            let mut j = self.left[i];
+            while j != i{
+                self.size[self.column[j]] += 1;
+                self.down[self.up[j]] = j;
+                self.up[self.down[j]] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        self.right[self.left[c]] = c;
+        self.left[self.right[c]] = c;
+    }
+}
+
+/// Sudoku solver backend using Dancing Links.
+///
+/// Every `(cell, digit)` pair is a row. The cell constraints and the
+/// unit-digit constraints of the rules (row-digit, column-digit and
+/// section-digit for the classic rules) are the columns; units smaller than
+/// the board are secondary columns. Other parts of the rules only prune the
+/// rows before the search and are checked on every answer, so this backend
+/// is slow on e.g. Killer boards without givens.
+pub struct DancingLinksSolver{
+    /// Decides the order in which rows are tried.
+    rng : ChaChaRng,
+}
+
+impl Default for DancingLinksSolver{
+    fn default() -> DancingLinksSolver{
+        DancingLinksSolver::new()
+    }
+}
+
+impl DancingLinksSolver{
+    /// Create new DancingLinksSolver
+    pub fn new() -> DancingLinksSolver{
+        DancingLinksSolver{
+            rng : ChaChaRng::from_entropy(),
+        }
+    }
+
+    /// Create new DancingLinksSolver whose answers are reproducible from `seed`
+    pub fn from_seed(seed: u64) -> DancingLinksSolver{
+        DancingLinksSolver{
+            rng : ChaChaRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Create new DancingLinksSolver seeded by another random number generator
+    pub fn from_rng<R: RngCore>(rng: &mut R) -> DancingLinksSolver{
+        DancingLinksSolver{
+            rng : ChaChaRng::from_seed(rng.gen()),
+        }
+    }
+
+    /// Build the exact cover matrix of a gameboard.
+    fn make_matrix(&mut self,gb: &Gameboard) -> ExactCover{
+        let n = gb.size();
+        let mut units = gb.units();
+        // full units first: they are the primary columns
+        units.sort_by_key(|unit| unit.len() != n);
+        let n_full = units.iter().filter(|unit| unit.len() == n).count();
+        let mut matrix = ExactCover::with_secondary(n*n + n_full*n,(units.len() - n_full)*n);
+
+        // units of every cell
+        let mut cell_units = vec![vec![];n*n];
+        for (k,unit) in units.iter().enumerate(){
+            for &(i,j) in unit.iter(){
+                cell_units[i*n + j].push(k);
+            }
+        }
+
+        // digits the rules allow before the search; a contradiction leaves no rows
+        let mut candidates = gb.shape().cells()
+            .map(|ind| match gb.get(ind){
+                0 => gb.shape().all_digits(),
+                d => 1 << d,
+            })
+            .collect::<Vec<u32>>();
+        if !gb.rules().iter().all(|rule| rule.prune(gb.shape(),&mut candidates)){
+            return matrix;
+        }
+
+        // rows are inserted in random order so that the first answer varies
+        let mut rows = (0..n*n*n).collect::<Vec<usize>>();
+        shuffle(&mut self.rng,&mut rows);
+
+        for row_id in rows{
+            let (pos,d) = (row_id/n,(row_id%n) as u8 + 1);
+
+            if candidates[pos] & (1 << d) != 0{
+                matrix.add_row(row_id,&columns(n,&cell_units[pos],pos,d));
+            }
+        }
+
+        matrix
+    }
+}
+
+impl SudokuSolver for DancingLinksSolver{
+    fn for_each_answer(&mut self,gb: &Gameboard,f: &mut dyn FnMut(&Grid) -> bool) -> usize{
+        let mut n = 0;
+
+        if gb.invalid(){
+            return n;
+        }
+
+        let size = gb.size();
+        self.make_matrix(gb).for_each_solution(&mut |rows|{
+            let mut cells = gb.shape().empty_grid();
+            for row_id in rows{
+                let pos = row_id / size;
+                cells[pos/size][pos%size] = (row_id % size) as u8 + 1;
+            }
+
+            if !gb.rules().iter().all(|rule| rule.validate(gb.shape(),&cells)){
+                return true;
+            }
+
+            n += 1;
+            f(&cells)
+        });
+
+        n
+    }
+
+    fn reseed(&mut self,seed: u64){
+        self.rng = ChaChaRng::seed_from_u64(seed);
+    }
+}
+
+/// Constraint columns covered by digit `d` at cell `pos`, which belongs to
+/// the units `units`.
+fn columns(n: usize,units: &[usize],pos: usize,d: u8) -> Vec<usize>{
+    let d = d as usize - 1;
+
+    std::iter::once(pos)
+        .chain(units.iter().map(|k| n*n + k*n + d))
+        .collect()
+}
+
+#[test]
+fn test_exact_cover(){
+    // example from Knuth's paper
+    let mut matrix = ExactCover::new(7);
+    matrix.add_row(0,&[2,4,5]);
+    matrix.add_row(1,&[0,3,6]);
+    matrix.add_row(2,&[1,2,5]);
+    matrix.add_row(3,&[0,3]);
+    matrix.add_row(4,&[1,6]);
+    matrix.add_row(5,&[3,4,6]);
+
+    let mut answers = matrix.solve(10);
+    assert_eq!(answers.len(),1);
+    answers[0].sort();
+    assert_eq!(answers[0],vec![0,3,4]);
+}
+
+#[test]
+fn test_dancing_links_sudoku(){
+    let gb = crate::solver::make_board(
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......");
+    let answers = DancingLinksSolver::new().make_answer_list(&gb,2);
+    let expected = crate::solver::Solver::new().make_answer_list(&gb,1);
+
+    assert_eq!(answers.len(),1);
+    assert_eq!(answers[0],expected[0]);
+}
+
+#[test]
+fn test_dancing_links_shape(){
+    let gb : Gameboard = "1..4.3.....2..1.".parse().unwrap();
+    let answers = DancingLinksSolver::new().make_answer_list(&gb,2);
+
+    assert_eq!(answers,vec![vec![vec![1,2,3,4],vec![4,3,2,1],vec![3,1,4,2],vec![2,4,1,3]]]);
+}
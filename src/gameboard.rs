@@ -1,254 +1,744 @@
-use crate::solver::Solver;
-use crate::generator::Generator;
-
-/// Size of game board.
+//! Gameboard model.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+
+use crate::solver::{SolutionCount,Solver,SudokuSolver};
+use crate::generator::{Budget,Generator,GenerateError,GeneratorOptions};
+use crate::logical_solver::{LogicalSolver,Step};
+use crate::rating::{self,Difficulty,Rating};
+use crate::shape::{self,Grid,Pos,Shape};
+use crate::constraint::{self,Boxes,Constraint,Rule};
+use crate::killer::{Cage,Cages};
+use crate::jigsaw::Regions;
+#[cfg(test)]
+use crate::constraint::{Columns,Rows};
+
+/// Size of the classic game board.
 pub const SIZE : usize = 9;
 
+/// Error of parsing a gameboard from the line format
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum ParseBoardError{
+    /// The number of characters isn't the number of cells of a supported size.
+    Length(usize),
+    /// A character other than a digit of the board or `.` was found.
+    Character{
+        /// Position in the line (0-based)
+        index : usize,
+        /// The character
+        ch : char,
+    },
+}
+
+impl fmt::Display for ParseBoardError{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ParseBoardError::Length(n) =>
+                write!(f,"expected {} cells (or n*n for another size) but found {}",SIZE*SIZE,n),
+            ParseBoardError::Character{index,ch} =>
+                write!(f,"illegal character {:?} at position {}",ch,index+1),
+        }
+    }
+}
+
+impl Error for ParseBoardError{}
+
+/// Kind of pencil marks
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum NoteKind{
+    /// Drawn as a small digit grid shaped like a box, each digit at a fixed place
+    Corner,
+    /// Drawn as a row of digits in the middle of the cell
+    Centre,
+    /// Candidates kept up to date by the auto-candidate mode.
+    /// Drawn like corner marks, which are hidden meanwhile.
+    Auto,
+}
+
+impl NoteKind{
+    /// All kinds
+    pub const ALL : [NoteKind;3] = [NoteKind::Corner,NoteKind::Centre,NoteKind::Auto];
+}
+
 /// Stores game board information
+#[derive(Clone)]
 pub struct Gameboard{
+    /// Size of the boxes
+    shape : Shape,
+    /// Rules of the board, the classic rules by default
+    rules : Vec<Rule>,
+    /// Cages of a Killer board, also part of `rules`
+    cages : Vec<Cage>,
+    /// Regions of a Jigsaw board, replacing the boxes in `rules`
+    regions : Option<Regions>,
     /// Stores the content of the cells
     /// `0` is an empty cell.
-    cells : [[u8;SIZE];SIZE],
+    cells : Grid,
     /// Flags of readonly
-    readonly : [[bool;SIZE]; SIZE],
+    readonly : Vec<Vec<bool>>,
     /// Flag of invalid
-    invalid_pos : [[bool;SIZE];SIZE],
+    invalid_pos : Vec<Vec<bool>>,
+    /// Seed of the generated quiz
+    seed : Option<u64>,
+    /// Corner pencil marks. Bit `d` is set if digit `d` is noted.
+    corner : Vec<Vec<u32>>,
+    /// Centre pencil marks. Bit `d` is set if digit `d` is noted.
+    centre : Vec<Vec<u32>>,
+    /// Candidates of the auto-candidate mode, `None` if the mode is off
+    auto : Option<Vec<Vec<u32>>>,
+    /// Answer of the quiz, cached at generation time
+    solution : Option<Grid>,
+}
+
+impl Default for Gameboard{
+    fn default() -> Gameboard{
+        Gameboard::new()
+    }
 }
 
 impl Gameboard{
-    /// Creates a new game board
+    /// Creates a new classic 9x9 game board
     pub fn new() -> Gameboard{
+        Gameboard::with_shape(Shape::CLASSIC)
+    }
+
+    /// Creates a new game board with boxes of `shape`
+    pub fn with_shape(shape: Shape) -> Gameboard{
+        let n = shape.size();
+
         Gameboard{
-            cells : [[0;SIZE];SIZE],
-            readonly : [[false; SIZE];SIZE],
-            invalid_pos: [[false; SIZE];SIZE],
+            shape,
+            rules : constraint::classic(),
+            cages : vec![],
+            regions : None,
+            cells : shape.empty_grid(),
+            readonly : vec![vec![false;n];n],
+            invalid_pos: vec![vec![false;n];n],
+            seed : None,
+            corner : vec![vec![0;n];n],
+            centre : vec![vec![0;n];n],
+            auto : None,
+            solution : None,
         }
     }
 
-    /// Gets the character at cell location
-    pub fn char(&self,ind:(usize,usize)) -> Option<char>{
-        match self.cells[ind.0][ind.1]{
-            n if 1<= n && n <= 9 => 
-                Some(('0' as u8 + n) as char),
-            _ => 
-                None,
+    /// Size of the boxes
+    pub fn shape(&self) -> Shape{
+        self.shape
+    }
+
+    /// Number of rows (columns, boxes, digits)
+    pub fn size(&self) -> usize{
+        self.shape.size()
+    }
+
+    /// Rules of the board
+    pub fn rules(&self) -> &[Rule]{
+        &self.rules
+    }
+
+    /// Replace the rules of the board. Without a `cages` rule the board
+    /// isn't a Killer board anymore, without a `regions` rule it isn't a
+    /// Jigsaw board.
+    pub fn set_rules(&mut self,rules: Vec<Rule>){
+        self.rules = rules;
+        if !self.has_rule("cages"){
+            self.cages.clear();
+        }
+        if !self.has_rule("regions"){
+            self.regions = None;
         }
+        self.invalid_pos = self.search_invalid_position();
+        if self.auto.is_some(){
+            self.set_auto_candidates(true);
+        }
+    }
+
+    /// if the board has a rule named `name`, returns true
+    pub fn has_rule(&self,name: &str) -> bool{
+        self.rules.iter().any(|rule| rule.name() == name)
+    }
+
+    /// Add a rule to the rules of the board
+    pub fn add_rule(&mut self,rule: Rule){
+        let mut rules = self.rules.clone();
+        rules.push(rule);
+        self.set_rules(rules);
+    }
+
+    /// Cages of a Killer board
+    pub fn cages(&self) -> &[Cage]{
+        &self.cages
+    }
+
+    /// Make this a Killer board with `cages`, replacing earlier cages.
+    /// The board stays a Killer board even if `cages` is empty.
+    pub fn set_cages(&mut self,cages: Vec<Cage>){
+        let mut rules = self.rules.iter()
+            .filter(|rule| rule.name() != "cages")
+            .cloned()
+            .collect::<Vec<Rule>>();
+        rules.push(Rc::new(Cages::new(cages.clone())));
+        self.set_rules(rules);
+        self.cages = cages;
+    }
+
+    /// Remove the cages, leaving the other rules
+    pub fn clear_cages(&mut self){
+        let rules = self.rules.iter()
+            .filter(|rule| rule.name() != "cages")
+            .cloned()
+            .collect();
+        self.set_rules(rules);
+    }
+
+    /// Regions of a Jigsaw board
+    pub fn regions(&self) -> Option<&Regions>{
+        self.regions.as_ref()
+    }
+
+    /// Make this a Jigsaw board whose regions replace the boxes (or
+    /// earlier regions).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `regions` isn't of the size of the board.
+    pub fn set_regions(&mut self,regions: Regions){
+        assert_eq!(regions.size(),self.size(),"regions of another board size");
+
+        let region : Rule = Rc::new(regions.clone());
+        let mut rules = self.rules.iter()
+            .filter(|rule| rule.name() != "regions")
+            .map(|rule| if rule.name() == "boxes" {region.clone()} else {rule.clone()})
+            .collect::<Vec<Rule>>();
+        if !rules.iter().any(|rule| rule.name() == "regions"){
+            rules.push(region);
+        }
+        self.set_rules(rules);
+        self.regions = Some(regions);
+    }
+
+    /// Restore the boxes in place of the regions
+    pub fn clear_regions(&mut self){
+        let rules = self.rules.iter()
+            .map(|rule| if rule.name() == "regions" {Rc::new(Boxes) as Rule} else {rule.clone()})
+            .collect();
+        self.set_rules(rules);
+    }
+
+    /// Region of a cell: its box, or its region on a Jigsaw board
+    pub fn region_of(&self,ind: Pos) -> usize{
+        match self.regions{
+            Some(ref regions) => regions.region_of(ind),
+            None => self.shape.box_of(ind),
+        }
+    }
+
+    /// Groups of cells which must hold different digits under the rules
+    pub fn units(&self) -> Vec<Vec<Pos>>{
+        constraint::units(&self.rules,self.shape)
+    }
+
+    /// Cells which share a group with `ind`
+    pub fn peers(&self,ind:Pos) -> Vec<Pos>{
+        let mut peers = self.units().into_iter()
+            .filter(|unit| unit.contains(&ind))
+            .flatten()
+            .filter(|&p| p != ind)
+            .collect::<Vec<Pos>>();
+        peers.sort();
+        peers.dedup();
+
+        peers
+    }
+
+    /// Gets the character at cell location
+    pub fn char(&self,ind:Pos) -> Option<char>{
+        shape::symbol(self.cells[ind.0][ind.1])
     }
 
     /// Set cell value.
-    pub fn set(&mut self,ind:(usize,usize),val : u8){
-        if self.readonly[ind.0][ind.1] == false{
+    ///
+    /// In auto-candidate mode the digit is removed from the candidates of
+    /// the peers, and an erased digit becomes a candidate again where possible.
+    pub fn set(&mut self,ind:Pos,val : u8){
+        if !self.readonly[ind.0][ind.1]{
+            let old = self.cells[ind.0][ind.1];
+            self.cells[ind.0][ind.1] = val;
+            self.invalid_pos = self.search_invalid_position();
+            self.update_auto(ind,old,val);
+        }
+    }
+
+    /// Set cell value without updating the auto-candidates.
+    /// Used to restore a recorded state together with its pencil marks.
+    pub fn set_raw(&mut self,ind:Pos,val : u8){
+        if !self.readonly[ind.0][ind.1]{
             self.cells[ind.0][ind.1] = val;
             self.invalid_pos = self.search_invalid_position();
         }
     }
 
     /// Get cell value
-    pub fn get(&self,ind:(usize,usize))->u8 {
+    pub fn get(&self,ind:Pos)->u8 {
         self.cells[ind.0][ind.1]
     }
 
     /// get all cell data
-    pub fn copy_cells(&self) -> [[u8;SIZE];SIZE]{
+    pub fn copy_cells(&self) -> Grid{
         self.cells.clone()
     }
 
+    /// Copy of the board holding only the givens (readonly cells)
+    pub fn givens(&self)->Gameboard{
+        let mut gb = self.clone();
+        for (i,j) in self.shape.cells(){
+            if !gb.readonly[i][j]{
+                gb.cells[i][j] = 0;
+            }
+        }
+        let n = self.size();
+        gb.invalid_pos = gb.search_invalid_position();
+        gb.corner = vec![vec![0;n];n];
+        gb.centre = vec![vec![0;n];n];
+        gb.auto = None;
+
+        gb
+    }
+
     /// get invalid value
-    pub fn get_invalid(&self,ind:(usize,usize))->bool{
+    pub fn get_invalid(&self,ind:Pos)->bool{
         self.invalid_pos[ind.0][ind.1]
     }
 
     /// get readonly value
-    pub fn get_readonly(&self,ind:(usize,usize))->bool{
+    pub fn get_readonly(&self,ind:Pos)->bool{
         self.readonly[ind.0][ind.1]
     }
 
     /// set readonly value
-    pub fn set_readonly(&mut self,ind:(usize,usize),val:bool){
+    pub fn set_readonly(&mut self,ind:Pos,val:bool){
         self.readonly[ind.0][ind.1] = val;
     }
 
+    /// Pencil marks of a cell. Bit `d` is set if digit `d` is noted.
+    pub fn notes(&self,ind:Pos,kind:NoteKind)->u32{
+        match kind{
+            NoteKind::Corner => self.corner[ind.0][ind.1],
+            NoteKind::Centre => self.centre[ind.0][ind.1],
+            NoteKind::Auto => self.auto.as_ref().map_or(0,|auto| auto[ind.0][ind.1]),
+        }
+    }
+
+    /// Replace the pencil marks of a cell. Readonly cells have no marks,
+    /// and `Auto` marks are only kept while the auto-candidate mode is on.
+    pub fn set_notes(&mut self,ind:Pos,kind:NoteKind,notes:u32){
+        if self.readonly[ind.0][ind.1]{
+            return;
+        }
+
+        match kind{
+            NoteKind::Corner => self.corner[ind.0][ind.1] = notes,
+            NoteKind::Centre => self.centre[ind.0][ind.1] = notes,
+            NoteKind::Auto => if let Some(ref mut auto) = self.auto{
+                auto[ind.0][ind.1] = notes;
+            },
+        }
+    }
+
+    /// if the auto-candidate mode is on then true
+    pub fn auto_candidates(&self)->bool{
+        self.auto.is_some()
+    }
+
+    /// Turn the auto-candidate mode on or off.
+    ///
+    /// Turning it on fills the `Auto` marks of every empty cell with its
+    /// candidates. The user's own corner and centre marks are kept aside
+    /// and are shown again when the mode is turned off.
+    pub fn set_auto_candidates(&mut self,on:bool){
+        self.auto = if on{
+            let n = self.size();
+            let mut auto = vec![vec![0;n];n];
+            for (i,j) in self.shape.cells(){
+                auto[i][j] = self.candidates((i,j));
+            }
+            Some(auto)
+        }else{
+            None
+        };
+    }
+
+    /// Digits which no peer (same row, column, section or other group) holds.
+    /// Filled and readonly cells have no candidates.
+    pub fn candidates(&self,ind:Pos)->u32{
+        if self.cells[ind.0][ind.1] != 0 || self.readonly[ind.0][ind.1]{
+            return 0;
+        }
+
+        self.peers(ind).iter()
+            .fold(self.shape.all_digits(),|c,&(i,j)| c & !(1 << self.cells[i][j]))
+    }
+
+    /// Keep the auto-candidates up to date after `old` at `ind` was replaced by `val`
+    fn update_auto(&mut self,ind:Pos,old:u8,val:u8){
+        let mut auto = match self.auto.take(){
+            Some(auto) => auto,
+            None => return,
+        };
+
+        let peers = self.peers(ind);
+        if old != 0{
+            // the erased digit may be possible again
+            for &(i,j) in peers.iter(){
+                if self.candidates((i,j)) & (1 << old) != 0{
+                    auto[i][j] |= 1 << old;
+                }
+            }
+        }
+        if val != 0{
+            for &(i,j) in peers.iter(){
+                auto[i][j] &= !(1 << val);
+            }
+        }
+        auto[ind.0][ind.1] = self.candidates(ind);
+
+        self.auto = Some(auto);
+    }
+
+    /// Add digit `d` to the pencil marks of a cell or remove it
+    pub fn toggle_note(&mut self,ind:Pos,kind:NoteKind,d:u8){
+        let notes = self.notes(ind,kind) ^ (1 << d);
+        self.set_notes(ind,kind,notes);
+    }
+
+    /// Remove all pencil marks of a cell
+    pub fn clear_notes(&mut self,ind:Pos){
+        self.set_notes(ind,NoteKind::Corner,0);
+        self.set_notes(ind,NoteKind::Centre,0);
+    }
+
+    /// Seed of the quiz if it was generated by `Generator`
+    pub fn seed(&self)->Option<u64>{
+        self.seed
+    }
+
+    /// set seed value
+    pub fn set_seed(&mut self,seed:Option<u64>){
+        self.seed = seed;
+    }
+
+    /// Answer of the quiz if it is known
+    pub fn solution(&self)->Option<&Grid>{
+        self.solution.as_ref()
+    }
+
+    /// set solution value
+    pub fn set_solution(&mut self,solution:Option<Grid>){
+        self.solution = solution;
+    }
+
+    /// Solve the givens and keep the answer if it is unique.
+    /// Returns `false` if the quiz has no unique answer.
+    pub fn cache_solution(&mut self)->bool{
+        self.solution = match self.givens().count_solutions(2){
+            SolutionCount::Unique(answer) => Some(answer),
+            _ => None,
+        };
+
+        self.solution.is_some()
+    }
+
+    /// if the user entry of a cell differs from the known answer, returns true
+    pub fn wrong(&self,ind:Pos)->bool{
+        let d = self.cells[ind.0][ind.1];
+
+        d != 0 && !self.readonly[ind.0][ind.1]
+            && self.solution.as_ref().is_some_and(|s| s[ind.0][ind.1] != d)
+    }
+
     /// Check whether valid or invalid
     pub fn invalid(&self)->bool{
-        (0..SIZE*SIZE)
-            .map(|i| self.invalid_pos[i/SIZE][i%SIZE])
+        self.shape.cells()
+            .map(|(i,j)| self.invalid_pos[i][j])
             .fold(false, |s,x| s | x)
     }
 
     /// if game was finished, returns true
     pub fn finished(&self)->bool{
-        let filled = (0..SIZE*SIZE)
-            .map(|i| self.cells[i%SIZE][i/SIZE] != 0)
+        let filled = self.shape.cells()
+            .map(|(i,j)| self.cells[i][j] != 0)
             .fold(true,|s,i| s & i);
         
-        filled && self.invalid() == false
+        filled && !self.invalid()
     }
 
     /// Solve and fill answer.
     pub fn solve(&mut self){
-        let mut solver = Solver::new();
-        let answers = solver.make_answer_list(self,1);
+        self.solve_with(&mut Solver::new());
+    }
+
+    /// Solve with a specific solver backend and fill answer.
+    pub fn solve_with(&mut self,solver: &mut dyn SudokuSolver){
+        let mut answers = solver.make_answer_list(self,1);
+
+        if !answers.is_empty(){
+            self.cells = answers.remove(0);
+        }
+    }
+
+    /// Solve with human techniques only and fill the cells which could be
+    /// deduced. Returns the applied steps in order.
+    pub fn solve_logical(&mut self)->Vec<Step>{
+        let steps = LogicalSolver::new(self).solve();
 
-        if answers.len() > 0{
-            self.cells = answers[0];
+        for step in steps.iter(){
+            for &(pos,d) in step.placements.iter(){
+                self.set(pos,d);
+            }
         }
+
+        steps
+    }
+
+    /// Rate the difficulty of the current cells
+    pub fn rate(&self)->Rating{
+        rating::rate(self)
     }
 
     /// if board has only one answer then true
-    pub fn unique(&mut self)->bool{
+    pub fn unique(&self)->bool{
+        self.unique_with(&mut Solver::new())
+    }
+
+    /// Same as `unique` but with a specific solver backend.
+    pub fn unique_with(&self,solver: &mut dyn SudokuSolver)->bool{
+        solver.count_solutions(self,2).is_unique()
+    }
+
+    /// Count answers, stopping at `cap` answers.
+    pub fn count_solutions(&self,cap: usize)->SolutionCount{
+        Solver::new().count_solutions(self,cap)
+    }
+
+    /// if board has a unique answer and no given can be removed without
+    /// losing uniqueness, returns true
+    pub fn is_minimal(&self)->bool{
         let mut solver = Solver::new();
-        let answers = solver.make_answer_list(self,2);
 
-        answers.len() == 1
+        if !self.unique_with(&mut solver){
+            return false;
+        }
+
+        let mut gb = self.clone();
+        for (i,j) in self.shape.cells(){
+            let original = gb.cells[i][j];
+            if original == 0{
+                continue;
+            }
+
+            gb.cells[i][j] = 0;
+            if gb.unique_with(&mut solver){
+                return false;
+            }
+            gb.cells[i][j] = original;
+        }
+
+        true
     }
 
-    /// Set 0 into all cells
+    /// Set 0 into all cells. The rules, cages and regions are kept.
     pub fn reset(&mut self){
-        self.cells = [[0;SIZE];SIZE];
-        self.readonly = [[false;SIZE];SIZE];
-        self.invalid_pos = [[false;SIZE];SIZE];
+        let rules = self.rules.clone();
+        let cages = self.cages.clone();
+        let regions = self.regions.clone();
+        *self = Gameboard::with_shape(self.shape);
+        self.rules = rules;
+        self.cages = cages;
+        self.regions = regions;
     }
 
-    /// Generate new Sudoku quiz
-    pub fn generate(&mut self){
-        let mut generator = Generator::new();
+    /// Change the size of the boxes. Clears the board and restores the
+    /// classic rules.
+    pub fn set_shape(&mut self,shape: Shape){
+        *self = Gameboard::with_shape(shape);
+    }
 
-        generator.generate(self);
+    /// Generate new Sudoku quiz and return its seed
+    pub fn generate(&mut self)->Result<u64,GenerateError>{
+        let mut generator = self.generator();
+
+        generator.generate(self)
     }
-    /// Search invalid area
-    fn search_invalid_position(&self) -> [[bool;SIZE]; SIZE]{
-        let mut invalid1 = self.search_invalid_position_section();
-        let invalid2 = self.search_invalid_position_row();
-        let invalid3 = self.search_invalid_position_col();
 
-        for (i,j) in (0..SIZE*SIZE).map(|n| (n/SIZE,n%SIZE)){
-            invalid1[i][j] |= invalid2[i][j] || invalid3[i][j];
-        }
-        
-        invalid1
+    /// Generate the Sudoku quiz identified by `seed`
+    pub fn generate_from_seed(&mut self,seed: u64)->Result<(),GenerateError>{
+        let mut generator = self.generator();
+
+        generator.generate_from_seed(self,seed)
     }
 
-    /// Search invalid position in specific area
-    fn search_invalid_position_in(&self,inds : [(usize,usize);SIZE])->Vec<(usize,usize)>{
-        let mut v = vec![];
+    /// Generate new Sudoku quiz whose difficulty is within `range` and return its seed
+    pub fn generate_with_difficulty(&mut self,range: RangeInclusive<Difficulty>)->Result<u64,GenerateError>{
+        let mut generator = self.generator();
 
-        let counter = { 
-            let mut temp = [0;SIZE+1];
-            for (i,j) in inds.iter(){
-                temp[ self.cells[*i][*j] as usize]+=1;
-            }
-            temp[0] = 0;
+        generator.generate_with_difficulty(self,range,&Budget::default())
+    }
 
-            temp
-        };
+    /// Generate new Jigsaw quiz on a random layout, within `range` if given,
+    /// and return its seed
+    pub fn generate_jigsaw(&mut self,range: Option<RangeInclusive<Difficulty>>)->Result<u64,GenerateError>{
+        let seed = rand::random::<u64>();
 
-        for (i,j) in inds.iter(){
-            if counter[ self.cells[*i][*j] as usize] > 1{
-                v.push((*i,*j));
-            }
-        }
+        self.generate_jigsaw_from_seed(seed,range).map(|_| seed)
+    }
 
-        v
+    /// Generate the Jigsaw quiz identified by `seed`. The layout is drawn
+    /// from the seed too, so the seed alone reproduces the quiz.
+    pub fn generate_jigsaw_from_seed(&mut self,seed: u64,range: Option<RangeInclusive<Difficulty>>)
+        ->Result<(),GenerateError>
+    {
+        self.set_regions(Regions::random(self.shape,&mut ChaChaRng::seed_from_u64(seed)));
+        let mut generator = self.generator();
+
+        match range{
+            Some(range) => generator.generate_with_difficulty_from_seed(self,seed,range,&Budget::default()),
+            None => generator.generate_from_seed(self,seed),
+        }
     }
 
-    /// Search invalid area(section)
-    fn search_invalid_position_section(&self)->[[bool;SIZE];SIZE]{
-        let mut invalid = [[false;SIZE];SIZE];
+    /// Generator with the default options of the board size
+    fn generator(&self)->Generator{
+        let mut generator = Generator::new();
+        generator.set_options(GeneratorOptions::for_shape(self.shape))
+            .expect("default options are valid");
 
-        for (sec_i,sec_j) in (0..SIZE).map(|i| (i/3,i%3)){
-            let area_index : [(usize,usize);SIZE] = {
-                let mut temp = [(0,0); SIZE];
+        generator
+    }
 
-                for n in 0..SIZE{
-                    temp[n] = (3*sec_i + (n/3), 3*sec_j + (n%3) );
-                } 
+    /// Search invalid area: the conflicts of every rule
+    fn search_invalid_position(&self) -> Vec<Vec<bool>>{
+        let n = self.size();
+        let mut invalid = vec![vec![false;n];n];
 
-                temp
-            };
-            for (i,j) in self.search_invalid_position_in(area_index){
-                invalid[i][j] = true;
+        for rule in self.rules.iter(){
+            let invalid2 = self.search_invalid_position_rule(rule.as_ref());
+            for (i,j) in self.shape.cells(){
+                invalid[i][j] |= invalid2[i][j];
             }
         }
 
         invalid
     }
 
-    /// Search invalid area(row)
-    fn search_invalid_position_row(&self) -> [[bool;SIZE]; SIZE]{
-        let mut invalid = [[false;SIZE];SIZE];
+    /// Search conflicts of a single rule
+    fn search_invalid_position_rule(&self,rule : &dyn Constraint)->Vec<Vec<bool>>{
+        let n = self.size();
+        let mut invalid = vec![vec![false;n];n];
 
-        for i in 0..SIZE{
-            let area_index = {
-                let mut temp = [(0,0);SIZE];
-                for j in 0..SIZE{
-                    temp[j] = (i,j);
-                }
-                temp
-            };
-            for (i,j) in self.search_invalid_position_in(area_index){
-                invalid[i][j] = true;
-            }
+        for (i,j) in rule.conflicts(self.shape,&self.cells){
+            invalid[i][j] = true;
         }
 
         invalid
     }
+}
 
-    /// Search invalid area(col)
-    fn search_invalid_position_col(&self) -> [[bool;SIZE]; SIZE]{
-        let mut invalid = [[false;SIZE];SIZE];
+impl FromStr for Gameboard{
+    type Err = ParseBoardError;
+
+    /// Parse the line format: one character per cell, row by row.
+    ///
+    /// The size of the board follows from the length of the line, e.g. 81
+    /// characters for the classic board and 36 for a 6x6 board with 3x2 boxes.
+    /// Digits above 9 are letters (`A` is 10). Digits are givens (readonly),
+    /// `.` and `0` are empty cells. Surrounding whitespace is ignored.
+    fn from_str(line: &str) -> Result<Gameboard,ParseBoardError>{
+        let line = line.trim();
+        let n = line.chars().count();
+        let size = (1..=n).take_while(|k| k*k <= n).last().unwrap_or(0);
+        let shape = match Shape::from_size(size){
+            Some(shape) if size*size == n => shape,
+            _ => return Err(ParseBoardError::Length(n)),
+        };
 
-        for j in 0..SIZE{
-            let area_index = {
-                let mut temp = [(0,0);SIZE];
-                for i in 0..SIZE{
-                    temp[i] = (i,j);
-                }
-                temp
-            };
+        let mut gb = Gameboard::with_shape(shape);
+        for (index,ch) in line.chars().enumerate(){
+            let ind = (index/size,index%size);
+            match (ch,shape::digit(ch)){
+                ('.',_) | ('0',_) => {},
+                (_,Some(d)) if d as usize <= size => {
+                    gb.set(ind,d);
+                    gb.set_readonly(ind,true);
+                },
+                _ => return Err(ParseBoardError::Character{index,ch}),
+            }
+        }
+
+        Ok(gb)
+    }
+}
 
-            for (i,j) in self.search_invalid_position_in(area_index){
-                invalid[i][j] = true;
+impl fmt::Display for Gameboard{
+    /// Write the line format with `.` for empty cells.
+    ///
+    /// The alternate form (`{:#}`) writes only the givens (readonly cells).
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        for (i,j) in self.shape.cells(){
+            let hidden = f.alternate() && !self.readonly[i][j];
+            match self.char((i,j)){
+                Some(ch) if !hidden => write!(f,"{}",ch)?,
+                _ => write!(f,".")?,
             }
         }
 
-        invalid
+        Ok(())
     }
 }
 
 #[test]
 fn test_invalid_col(){
     let mut gb = Gameboard::new();
-    let mut invalid = [[false;SIZE];SIZE];
 
     gb.set((0,0),1);
     gb.set((1,0),1);
-    invalid[0][0] = true;
-    invalid[1][0] = true;
-    assert_eq!(gb.search_invalid_position_col(),invalid);
+    assert_eq!(Columns.conflicts(gb.shape(),&gb.copy_cells()),vec![(0,0),(1,0)]);
+    assert!(Rows.conflicts(gb.shape(),&gb.copy_cells()).is_empty());
 }
 
 #[test]
 fn test_invalid_row(){
     let mut gb = Gameboard::new();
-    let mut invalid = [[false;SIZE];SIZE];
 
     gb.set((0,0),1);
     gb.set((0,1),1);
-    invalid[0][0] = true;
-    invalid[0][1] = true;
-    assert_eq!(gb.search_invalid_position_row(),invalid);
+    assert_eq!(Rows.conflicts(gb.shape(),&gb.copy_cells()),vec![(0,0),(0,1)]);
+    assert!(Columns.conflicts(gb.shape(),&gb.copy_cells()).is_empty());
 }
 
 #[test]
 fn test_invalid_section(){
     let mut gb = Gameboard::new();
-    let mut invalid = [[false;SIZE];SIZE];
 
     gb.set((0,0),1);
-    gb.set((0,1),1);
-    invalid[0][0] = true;
-    invalid[0][1] = true;
-    assert_eq!(gb.search_invalid_position_section(),invalid);
+    gb.set((1,1),1);
+    assert_eq!(Boxes.conflicts(gb.shape(),&gb.copy_cells()),vec![(0,0),(1,1)]);
+    assert!(Rows.conflicts(gb.shape(),&gb.copy_cells()).is_empty());
+}
+
+#[test]
+fn test_not_minimal(){
+    let mut gb = Gameboard::new();
+    gb.solve();
+
+    assert!(!gb.is_minimal());
 }
 
 #[test]
@@ -258,6 +748,136 @@ fn test_invalid_in(){
     gb.set((0,0),1);
 
     let area_index = [(0,0);SIZE];
-    assert_eq!(gb.search_invalid_position_in(area_index),vec![(0,0);SIZE]);
+    assert_eq!(constraint::duplicates(&gb.copy_cells(),&area_index),vec![(0,0);SIZE]);
+}
+
+
+#[test]
+fn test_parse_roundtrip(){
+    let line = "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+    let mut gb : Gameboard = line.replace('.',"0").parse().unwrap();
+
+    assert!(gb.get_readonly((0,2)));
+    assert!(!gb.get_readonly((0,0)));
+    assert_eq!(gb.to_string(),line);
+
+    gb.set((0,0),4);
+    assert_eq!(&gb.to_string()[..3],"4.3");
+    assert_eq!(format!("{:#}",gb),line);
+}
+
+#[test]
+fn test_parse_error(){
+    assert_eq!("123".parse::<Gameboard>().err(),Some(ParseBoardError::Length(3)));
+
+    let line = format!("12x{}",".".repeat(78));
+    assert_eq!(line.parse::<Gameboard>().err(),Some(ParseBoardError::Character{index : 2,ch : 'x'}));
+}
+
+#[test]
+fn test_notes(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+
+    gb.toggle_note((0,0),NoteKind::Corner,4);
+    gb.toggle_note((0,0),NoteKind::Corner,5);
+    gb.toggle_note((0,0),NoteKind::Centre,4);
+    gb.toggle_note((0,0),NoteKind::Corner,5);
+    assert_eq!(gb.notes((0,0),NoteKind::Corner),1 << 4);
+    assert_eq!(gb.notes((0,0),NoteKind::Centre),1 << 4);
+
+    // givens have no notes
+    gb.toggle_note((0,2),NoteKind::Corner,1);
+    assert_eq!(gb.notes((0,2),NoteKind::Corner),0);
+
+    assert_eq!(gb.givens().notes((0,0),NoteKind::Corner),0);
+    gb.clear_notes((0,0));
+    assert_eq!(gb.notes((0,0),NoteKind::Centre),0);
+}
+
+#[test]
+fn test_auto_candidates(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    gb.toggle_note((0,0),NoteKind::Corner,4);
+
+    gb.set_auto_candidates(true);
+    assert_eq!(gb.notes((0,0),NoteKind::Auto),gb.candidates((0,0)));
+    assert_eq!(gb.notes((0,0),NoteKind::Auto),1 << 4 | 1 << 5);
+
+    // placing a digit eliminates it from the peers
+    gb.set((1,1),5);
+    assert_eq!(gb.notes((0,0),NoteKind::Auto),1 << 4);
+    gb.set((1,1),0);
+    assert_eq!(gb.notes((0,0),NoteKind::Auto),1 << 4 | 1 << 5);
+
+    // the user's notes come back
+    gb.set_auto_candidates(false);
+    assert_eq!(gb.notes((0,0),NoteKind::Auto),0);
+    assert_eq!(gb.notes((0,0),NoteKind::Corner),1 << 4);
+}
+
+#[test]
+fn test_wrong(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+
+    // the answer of (0,0) is 4, but 5 doesn't conflict yet
+    gb.set((0,0),5);
+    assert!(!gb.invalid());
+    assert!(!gb.wrong((0,0)));
+
+    assert!(gb.cache_solution());
+    assert!(gb.wrong((0,0)));
+    gb.set((0,0),4);
+    assert!(!gb.wrong((0,0)));
+    assert!(!gb.wrong((0,2)));
+
+    let mut gb = Gameboard::new();
+    assert!(!gb.cache_solution());
+    gb.generate_from_seed(3).unwrap();
+    let solution = gb.solution().cloned();
+    gb.solve();
+    assert_eq!(solution,Some(gb.copy_cells()));
 }
 
+#[test]
+fn test_shapes(){
+    // 4x4 board with 2x2 boxes
+    let mut gb : Gameboard = "1..4.3.....2..1.".parse().unwrap();
+    assert_eq!(gb.shape(),Shape{box_width : 2,box_height : 2});
+    assert!(gb.unique());
+    gb.solve();
+    assert!(gb.finished());
+    assert_eq!(gb.to_string(),"1234432131422413");
+
+    // 16x16 boards use letters
+    let mut gb = Gameboard::with_shape(Shape::from_size(16).unwrap());
+    gb.set((0,0),16);
+    assert_eq!(gb.char((0,0)),Some('G'));
+    gb.set((0,15),16);
+    assert!(gb.get_invalid((0,0)));
+    assert_eq!(gb.candidates((1,1)) & (1 << 16),0);
+    assert!(format!("{}",gb).parse::<Gameboard>().is_ok());
+
+    assert_eq!("123456.".parse::<Gameboard>().err(),Some(ParseBoardError::Length(7)));
+    assert!(format!("5{}",".".repeat(15)).parse::<Gameboard>().is_err());
+}
+
+#[test]
+fn test_jigsaw_seed(){
+    let mut gb = Gameboard::new();
+    let seed = gb.generate_jigsaw(None).unwrap();
+    assert_eq!(gb.seed(),Some(seed));
+    assert_ne!(gb.regions(),Some(&Regions::boxes(Shape::CLASSIC)));
+
+    // a fresh board regenerates the same layout and quiz
+    let mut gb2 = Gameboard::new();
+    gb2.generate_jigsaw_from_seed(seed,None).unwrap();
+    assert_eq!(gb2.regions(),gb.regions());
+    assert_eq!(gb2.to_string(),gb.to_string());
+    assert_eq!(gb2.solution(),gb.solution());
+}
@@ -1,7 +1,39 @@
 //! Gameboard controller.
 
+use std::io;
+use std::path::PathBuf;
+
 use piston::input::GenericEvent;
-use crate::gameboard::{Gameboard,SIZE};
+use crate::constraint;
+use crate::gameboard::{Gameboard,NoteKind};
+use crate::generator::GenerateError;
+use crate::history::History;
+use crate::hint::Hint;
+use crate::mistakes::MistakeTracker;
+use crate::rating::{Difficulty,Rating};
+use crate::save::{LoadError,SaveGame};
+use crate::shape::Shape;
+use crate::stats::Stats;
+use crate::timer::Timer;
+
+/// Default location of the save file.
+pub const SAVE_PATH : &str = "sudoku.save";
+
+/// Default location of the statistics file.
+pub const STATS_PATH : &str = "sudoku.stats";
+
+/// Mistakes allowed when the limit is turned on.
+pub const MAX_MISTAKES : u32 = 3;
+
+/// Board sizes the size key cycles through.
+pub const SHAPES : [Shape;6] = [
+    Shape{box_width : 2,box_height : 2},
+    Shape{box_width : 3,box_height : 2},
+    Shape::CLASSIC,
+    Shape{box_width : 4,box_height : 3},
+    Shape{box_width : 4,box_height : 4},
+    Shape{box_width : 5,box_height : 5},
+];
 
 /// Handles events for Sudoku game.
 pub struct GameboardController{
@@ -9,23 +41,145 @@ pub struct GameboardController{
     pub gameboard : Gameboard,
     /// Selected cell.
     pub selected_cell: Option<(usize,usize)>,
+    /// Difficulty rating of the current quiz.
+    pub rating: Rating,
+    /// Difficulty of newly generated quizzes.
+    pub target: Difficulty,
+    /// Target the current quiz was generated for and why it was missed, if it was.
+    pub missed_target: Option<(Difficulty,GenerateError)>,
+    /// Board size of newly generated quizzes.
+    pub shape: Shape,
+    /// Newly generated quizzes are Sudoku X (the diagonals hold every digit).
+    pub diagonals: bool,
+    /// Newly generated quizzes are Killer Sudoku (cages and no givens).
+    pub killer: bool,
+    /// Newly generated quizzes are Jigsaw Sudoku with a random layout.
+    pub jigsaw: bool,
+    /// Save file used by the save/load keys.
+    pub save_path: PathBuf,
+    /// Undo/redo history of the current quiz.
+    pub history: History,
+    /// Digit keys toggle centre pencil marks instead of entering digits.
+    pub notes_mode: bool,
+    /// Hint being revealed.
+    pub hint: Option<Hint>,
+    /// Checks entries against the answer.
+    pub mistakes: MistakeTracker,
+    /// Playing time of the current quiz.
+    pub timer: Timer,
+    /// Statistics of finished games.
+    pub stats: Stats,
+    /// Statistics file.
+    pub stats_path: PathBuf,
+    /// Show the statistics instead of the board.
+    pub show_stats: bool,
+    /// The quiz was solved by the player.
+    pub completed: bool,
+    /// The quiz was solved faster than ever before at its level.
+    pub new_best: bool,
+    /// The result of the current quiz is already in the statistics.
+    recorded : bool,
+    /// Ctrl key is held down.
+    ctrl : bool,
+    /// Shift key is held down.
+    shift : bool,
+    /// Alt key is held down.
+    alt : bool,
     /// Stores last mouse cursor position
     cursor_pos : [f64;2],
 }
 
 impl GameboardController{
     /// Creates a new gameboard controller.
-    pub fn new(gameboard: Gameboard) -> GameboardController{
+    pub fn new(mut gameboard: Gameboard) -> GameboardController{
+        if gameboard.solution().is_none(){
+            gameboard.cache_solution();
+        }
+
         GameboardController{
-            gameboard : gameboard,
+            rating : gameboard.givens().rate(),
             selected_cell : None,
+            target : Difficulty::Medium,
+            missed_target : None,
+            shape : gameboard.shape(),
+            diagonals : gameboard.has_rule("diagonals"),
+            killer : gameboard.has_rule("cages"),
+            jigsaw : gameboard.regions().is_some(),
+            save_path : PathBuf::from(SAVE_PATH),
+            history : History::new(),
+            notes_mode : false,
+            hint : None,
+            mistakes : MistakeTracker::default(),
+            timer : Timer::new(),
+            stats : Stats::load(STATS_PATH).unwrap_or_else(|err|{
+                eprintln!("Could not load statistics: {}",err);
+                Stats::new()
+            }),
+            stats_path : PathBuf::from(STATS_PATH),
+            show_stats : false,
+            completed : false,
+            new_best : false,
+            recorded : gameboard.finished(),
+            ctrl : false,
+            shift : false,
+            alt : false,
             cursor_pos : [0.0,0.0],
+            gameboard : gameboard,
         }
     }
 
+    /// Creates a gameboard controller continuing a saved game.
+    pub fn resume(save: SaveGame) -> GameboardController{
+        let mut controller = GameboardController::new(save.gameboard);
+        controller.selected_cell = save.selected_cell;
+        controller.mistakes = save.mistakes;
+        controller.timer = Timer::paused(save.time);
+        controller.timer.resume();
+
+        controller
+    }
+
+    /// Write the current game to the save file.
+    pub fn save(&self) -> io::Result<()>{
+        let mut save = SaveGame::new(self.gameboard.clone(),self.selected_cell);
+        save.mistakes = self.mistakes.clone();
+        save.time = self.timer.elapsed();
+
+        save.save(&self.save_path)
+    }
+
+    /// Replace the current game by the one in the save file.
+    pub fn load(&mut self) -> Result<(),LoadError>{
+        let save = SaveGame::load(&self.save_path)?;
+        let auto = self.gameboard.auto_candidates();
+        self.gameboard = save.gameboard;
+        self.gameboard.set_auto_candidates(auto);
+        if self.gameboard.solution().is_none(){
+            self.gameboard.cache_solution();
+        }
+        self.selected_cell = save.selected_cell;
+        // the next quiz is of the same kind as the loaded one
+        self.shape = self.gameboard.shape();
+        self.diagonals = self.gameboard.has_rule("diagonals");
+        self.killer = self.gameboard.has_rule("cages");
+        self.jigsaw = self.gameboard.regions().is_some();
+        self.rating = self.gameboard.givens().rate();
+        self.history.clear();
+        self.hint = None;
+        self.missed_target = None;
+        self.mistakes = save.mistakes;
+        self.timer = Timer::paused(save.time);
+        self.timer.resume();
+        self.completed = false;
+        self.new_best = false;
+        self.recorded = self.gameboard.finished();
+
+        Ok(())
+    }
+
     /// Handles events.
     pub fn event<E: GenericEvent>(&mut self,board_pos: [f64;2], board_size:f64, e:&E){
-        use piston::input::{Button,MouseButton,Key};
+        use piston::input::{Button,MouseButton};
 
         if let Some(pos) = e.mouse_cursor_args() {
             self.cursor_pos = pos;
@@ -39,37 +193,315 @@ impl GameboardController{
             // Check hat coodinates are inside board boundaries.
             if x >= 0.0 && x < board_size && y >= 0.0 && y < board_size{
                 // Compute the cell position
-                let cell_x = (x / board_size * 9.0) as usize;
-                let cell_y = (y / board_size * 9.0) as usize;
+                let size = self.gameboard.size() as f64;
+                let cell_x = (x / board_size * size) as usize;
+                let cell_y = (y / board_size * size) as usize;
                 self.selected_cell = Some((cell_y,cell_x));
             }
         }
 
+        if let Some(Button::Keyboard(key)) = e.release_args(){
+            self.modifier(key,false);
+        }
+
         if let Some(Button::Keyboard(key)) = e.press_args(){
-            if let Some(ind) = self.selected_cell{
-                // Set cell value
-                match key{
-                    Key::D0 | Key::NumPad0 => self.gameboard.set(ind,0),
-                    Key::D1 | Key::NumPad1 => self.gameboard.set(ind,1),
-                    Key::D2 | Key::NumPad2 => self.gameboard.set(ind,2),
-                    Key::D3 | Key::NumPad3 => self.gameboard.set(ind,3),
-                    Key::D4 | Key::NumPad4 => self.gameboard.set(ind,4),
-                    Key::D5 | Key::NumPad5 => self.gameboard.set(ind,5),
-                    Key::D6 | Key::NumPad6 => self.gameboard.set(ind,6),
-                    Key::D7 | Key::NumPad7 => self.gameboard.set(ind,7),
-                    Key::D8 | Key::NumPad8 => self.gameboard.set(ind,8),
-                    Key::D9 | Key::NumPad9 => self.gameboard.set(ind,9),
-                    Key::Backspace => self.gameboard.set(ind,0),
-                    Key::Delete => self.gameboard.set(ind,0),
-                    Key::S => self.gameboard.solve(),
-                    Key::G => self.gameboard.generate(),
-                    Key::Up => self.arrow_key(-1, 0), 
-                    Key::Right=> self.arrow_key(0, 1), 
-                    Key::Left => self.arrow_key(0, -1), 
-                    Key::Down => self.arrow_key(1, 0), 
-                    _ => {},
+            self.key(key);
+            self.update_result();
+        }
+    }
+
+    /// Handles a key press.
+    fn key(&mut self,key: piston::input::Key){
+        use piston::input::Key;
+
+        self.modifier(key,true);
+
+        // Alt+A, Alt+B, ... enter the digits from 10 up on larger boards
+        if self.alt{
+            if let (Some(d),Some(ind)) = (letter_digit(key),self.selected_cell){
+                if !self.locked(){
+                    self.hint = None;
+                    self.digit(ind,d);
                 }
             }
+            return;
+        }
+
+        // keys which work even after the game is over
+        match key{
+            Key::F8 => if let Err(err) = self.save(){
+                eprintln!("Could not save game: {}",err);
+            },
+            Key::F9 => if let Err(err) = self.load(){
+                eprintln!("Could not load game: {}",err);
+            },
+            Key::G => self.generate(),
+            Key::F1 => self.target = Difficulty::Easy,
+            Key::F2 => self.target = Difficulty::Medium,
+            Key::F3 => self.target = Difficulty::Hard,
+            Key::F4 => self.target = Difficulty::Expert,
+            Key::F5 => self.target = Difficulty::Evil,
+            Key::F7 => self.shape = next_shape(self.shape),
+            Key::X => self.diagonals = !self.diagonals,
+            Key::K => self.killer = !self.killer,
+            Key::J => self.jigsaw = !self.jigsaw,
+            Key::F6 => self.mistakes.max_mistakes = match self.mistakes.max_mistakes{
+                Some(_) => None,
+                None => Some(MAX_MISTAKES),
+            },
+            Key::M => self.mistakes.mode = self.mistakes.mode.next(),
+            Key::N => self.notes_mode = !self.notes_mode,
+            Key::P if !self.recorded => self.timer.toggle(),
+            Key::T => self.show_stats = !self.show_stats,
+            _ => {},
+        }
+
+        // Ctrl+Z undo, Ctrl+Y or Ctrl+Shift+Z redo. They work after the game
+        // is over too, so that a solve or the last mistake can be taken back.
+        let paused = !self.recorded && !self.timer.is_running();
+        if self.ctrl && !self.show_stats && !paused{
+            let changed = match key{
+                Key::Z if self.shift => self.history.redo(&mut self.gameboard),
+                Key::Z => self.history.undo(&mut self.gameboard),
+                Key::Y => self.history.redo(&mut self.gameboard),
+                _ => false,
+            };
+            if changed{
+                self.hint = None;
+                return;
+            }
+        }
+
+        // the board is locked while paused and after the game is over
+        if self.locked(){
+            return;
+        }
+
+        match key{
+            Key::H => return self.hint(),
+            Key::LCtrl | Key::RCtrl | Key::LShift | Key::RShift | Key::LAlt | Key::RAlt => {},
+            // the hint may not fit the board anymore
+            _ => self.hint = None,
+        }
+
+        match key{
+            Key::A => {
+                let auto = self.gameboard.auto_candidates();
+                self.gameboard.set_auto_candidates(!auto);
+            },
+            Key::C => {self.mistakes.check(&self.gameboard);},
+            _ => {},
+        }
+
+        if let Some(ind) = self.selected_cell{
+            // Set cell value
+            match key{
+                Key::D0 | Key::NumPad0 => self.set(ind,0),
+                Key::D1 | Key::NumPad1 => self.digit(ind,1),
+                Key::D2 | Key::NumPad2 => self.digit(ind,2),
+                Key::D3 | Key::NumPad3 => self.digit(ind,3),
+                Key::D4 | Key::NumPad4 => self.digit(ind,4),
+                Key::D5 | Key::NumPad5 => self.digit(ind,5),
+                Key::D6 | Key::NumPad6 => self.digit(ind,6),
+                Key::D7 | Key::NumPad7 => self.digit(ind,7),
+                Key::D8 | Key::NumPad8 => self.digit(ind,8),
+                Key::D9 | Key::NumPad9 => self.digit(ind,9),
+                Key::Backspace => self.erase(ind),
+                Key::Delete => self.erase(ind),
+                Key::S => self.solve(),
+                Key::Up => self.arrow_key(-1, 0), 
+                Key::Right=> self.arrow_key(0, 1), 
+                Key::Left => self.arrow_key(0, -1), 
+                Key::Down => self.arrow_key(1, 0), 
+                _ => {},
+            }
+        }
+    }
+
+    /// if the board doesn't accept input then true
+    pub fn locked(&self) -> bool{
+        self.recorded || self.show_stats || !self.timer.is_running()
+    }
+
+    /// if the quiz is over (solved, lost or given up) then true
+    pub fn is_over(&self) -> bool{
+        self.recorded
+    }
+
+    /// Record the result once the quiz is solved or lost
+    fn update_result(&mut self){
+        if self.recorded{
+            return;
+        }
+
+        if self.gameboard.finished(){
+            self.timer.pause();
+            self.completed = true;
+            self.new_best = self.stats.add_win(self.rating.difficulty,self.timer.elapsed());
+            self.recorded = true;
+            self.save_stats();
+        }else if self.mistakes.game_over(){
+            self.record_loss();
+        }
+    }
+
+    /// Count the current quiz as lost
+    fn record_loss(&mut self){
+        if !self.recorded{
+            self.timer.pause();
+            self.stats.add_loss(self.rating.difficulty);
+            self.recorded = true;
+            self.save_stats();
+        }
+    }
+
+    /// Write the statistics file
+    fn save_stats(&self){
+        if let Err(err) = self.stats.save(&self.stats_path){
+            eprintln!("Could not save statistics: {}",err);
+        }
+    }
+
+    /// Reveal the next level of the hint, or apply it if everything is revealed
+    fn hint(&mut self){
+        match self.hint.take(){
+            None => {
+                self.hint = Hint::find(&self.gameboard);
+                if self.hint.is_none(){
+                    eprintln!("No hint available");
+                }
+            },
+            Some(mut hint) => if hint.reveal(){
+                self.hint = Some(hint);
+            }else{
+                // turning on the auto-candidate mode isn't part of the undo history
+                if !hint.step.eliminations.is_empty(){
+                    self.gameboard.set_auto_candidates(true);
+                }
+                let before = self.gameboard.clone();
+                hint.apply(&mut self.gameboard);
+                self.history.record(&before,&self.gameboard);
+            },
+        }
+    }
+
+    /// Set a cell value and record it in the history
+    fn set(&mut self,ind: (usize,usize),val: u8){
+        let before = self.gameboard.clone();
+        self.gameboard.set(ind,val);
+        self.history.record(&before,&self.gameboard);
+    }
+
+    /// Handle a digit key: Shift toggles a corner mark (a candidate in
+    /// auto-candidate mode), Ctrl or the notes mode a centre mark, otherwise
+    /// the digit is entered.
+    fn digit(&mut self,ind: (usize,usize),d: u8){
+        if d as usize > self.gameboard.size(){
+            return;
+        }
+
+        let kind = if self.shift && self.gameboard.auto_candidates(){
+            NoteKind::Auto
+        }else if self.shift{
+            NoteKind::Corner
+        }else if self.ctrl || self.notes_mode{
+            NoteKind::Centre
+        }else{
+            self.set(ind,d);
+            self.mistakes.entered(&self.gameboard,ind);
+            return;
+        };
+
+        // notes are only edited in empty cells
+        if self.gameboard.get(ind) == 0{
+            let before = self.gameboard.clone();
+            self.gameboard.toggle_note(ind,kind,d);
+            self.history.record(&before,&self.gameboard);
+        }
+    }
+
+    /// Erase the digit of a cell, or its pencil marks if it is empty
+    fn erase(&mut self,ind: (usize,usize)){
+        let before = self.gameboard.clone();
+        if self.gameboard.get(ind) != 0{
+            self.gameboard.set(ind,0);
+        }else{
+            self.gameboard.clear_notes(ind);
+        }
+        self.history.record(&before,&self.gameboard);
+    }
+
+    /// Fill the answer and record it in the history.
+    /// Giving up this way counts as a lost game.
+    fn solve(&mut self){
+        self.record_loss();
+
+        let before = self.gameboard.clone();
+        self.gameboard.solve();
+        self.history.record(&before,&self.gameboard);
+    }
+
+    /// Generate a new quiz of the target difficulty, size and rules and rate it.
+    /// The history of the previous quiz is dropped.
+    fn generate(&mut self){
+        // abandoning a started quiz counts as a lost game
+        let started = self.gameboard.shape().cells()
+            .any(|ind| !self.gameboard.get_readonly(ind) && self.gameboard.get(ind) != 0);
+        if started{
+            self.record_loss();
+        }
+
+        if self.gameboard.shape() != self.shape{
+            self.gameboard.set_shape(self.shape);
+            self.selected_cell = None;
+        }
+        if self.gameboard.has_rule("diagonals") != self.diagonals{
+            let rules = if self.diagonals {constraint::sudoku_x()} else {constraint::classic()};
+            self.gameboard.set_rules(rules);
+        }
+        if self.gameboard.has_rule("cages") != self.killer{
+            if self.killer{
+                self.gameboard.set_cages(vec![]);
+            }else{
+                self.gameboard.clear_cages();
+            }
+        }
+        if !self.jigsaw && self.gameboard.regions().is_some(){
+            self.gameboard.clear_regions();
+        }
+
+        // if the budget runs out, the last attempt is kept and the error shown.
+        // Killer quizzes have no givens to dig, so their difficulty isn't targeted.
+        let auto = self.gameboard.auto_candidates();
+        let result = if self.jigsaw{
+            let range = if self.killer {None} else {Some(self.target..=self.target)};
+            self.gameboard.generate_jigsaw(range)
+        }else if self.killer{
+            self.gameboard.generate()
+        }else{
+            self.gameboard.generate_with_difficulty(self.target..=self.target)
+        };
+        self.missed_target = result.err().map(|err| (self.target,err));
+        self.gameboard.set_auto_candidates(auto);
+        self.rating = self.gameboard.givens().rate();
+        self.history.clear();
+        self.hint = None;
+        self.mistakes.reset();
+        self.timer = Timer::new();
+        self.completed = false;
+        self.new_best = false;
+        self.recorded = false;
+    }
+
+    /// Track the state of modifier keys
+    fn modifier(&mut self,key: piston::input::Key,pressed: bool){
+        use piston::input::Key;
+
+        match key{
+            Key::LCtrl | Key::RCtrl => self.ctrl = pressed,
+            Key::LShift | Key::RShift => self.shift = pressed,
+            Key::LAlt | Key::RAlt => self.alt = pressed,
+            _ => {},
         }
     }
 
@@ -77,11 +509,77 @@ impl GameboardController{
     fn arrow_key(&mut self,dx:i32,dy:i32){
         if let Some(ind) = self.selected_cell{
             let (i,j) = ind;
-            let x = i as i32 + dx + SIZE as i32;
-            let y = j as i32 + dy + SIZE as i32;
-            let x = (x as usize) % SIZE;
-            let y = (y as usize) % SIZE;
+            let size = self.gameboard.size();
+            let x = i as i32 + dx + size as i32;
+            let y = j as i32 + dy + size as i32;
+            let x = (x as usize) % size;
+            let y = (y as usize) % size;
             self.selected_cell = Some((x,y));
         }
     }
-}
\ No newline at end of file
+}
+
+/// Digit entered with Alt and a letter key: `A` is 10, `B` 11 and so on
+fn letter_digit(key: piston::input::Key) -> Option<u8>{
+    use piston::input::Key;
+
+    let (code,a) = (key as u32,Key::A as u32);
+    if code >= a && code - a < 16{
+        Some((code - a) as u8 + 10)
+    }else{
+        None
+    }
+}
+
+/// Board size after `shape` in `SHAPES`
+fn next_shape(shape: Shape) -> Shape{
+    let k = SHAPES.iter().position(|&s| s == shape).map_or(0,|k| k + 1);
+
+    SHAPES[k % SHAPES.len()]
+}
+
+#[test]
+fn test_undo_solve(){
+    use piston::input::Key;
+
+    let gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    let mut controller = GameboardController::new(gb.clone());
+    controller.stats_path = std::env::temp_dir().join("sudoku-test-undo-solve.stats");
+    controller.selected_cell = Some((0,0));
+
+    controller.key(Key::S);
+    controller.update_result();
+    assert!(controller.gameboard.finished() && controller.is_over());
+
+    controller.key(Key::LCtrl);
+    controller.key(Key::Z);
+    assert_eq!(controller.gameboard.to_string(),gb.to_string());
+    // the loss stays recorded
+    assert!(controller.is_over());
+
+    controller.key(Key::Y);
+    assert!(controller.gameboard.finished());
+    let _ = std::fs::remove_file(&controller.stats_path);
+}
+
+#[test]
+fn test_load_settings(){
+    use crate::killer::Cage;
+
+    let mut gb = Gameboard::with_shape(Shape::from_size(6).unwrap());
+    gb.set_cages(vec![Cage::new(3,vec![(0,0),(0,1)])]);
+    let mut controller = GameboardController::new(gb);
+    controller.save_path = std::env::temp_dir().join("sudoku-test-load-settings.save");
+    controller.save().unwrap();
+
+    let mut controller2 = GameboardController::new(Gameboard::new());
+    controller2.save_path = controller.save_path.clone();
+    controller2.diagonals = true;
+    controller2.jigsaw = true;
+    controller2.load().unwrap();
+    assert_eq!(controller2.shape,Shape::from_size(6).unwrap());
+    assert!(controller2.killer && !controller2.diagonals && !controller2.jigsaw);
+    let _ = std::fs::remove_file(&controller.save_path);
+}
@@ -4,8 +4,25 @@ use graphics::types::Color;
 use graphics::{Context,Graphics};
 use graphics::character::CharacterCache;
 
+use crate::gameboard::{NoteKind,SIZE};
+use crate::shape::{self,Shape};
+use crate::hint::HintLevel;
+use crate::mistakes::CheckMode;
+use crate::rating::Difficulty;
+use crate::stats::Record;
+use crate::timer::format_duration;
 use crate::gameboard_controller::GameboardController;
 
+/// Digit drawn in the small digit grid of a cell
+struct Mark{
+    /// The digit
+    d : u8,
+    /// Upper left corner of the cell
+    pos : [f64;2],
+    /// Text color
+    color : Color,
+}
+
 /// Stores gameboard view settings.
 pub struct GameboardViewSettings{
     /// Position from left-top corner.
@@ -18,13 +35,13 @@ pub struct GameboardViewSettings{
     pub border_color: Color,
     /// Edge color around the whole board.
     pub board_edge_color: Color,
-    /// Edge color between the 3x3 sections.
+    /// Edge color between the sections (boxes).
     pub section_edge_color: Color,
     /// Edge color between cells.
     pub cell_edge_color: Color,
     /// Edge radius around the whole board.
     pub board_edge_radius: f64,
-    /// Edge radius between the 3x3 sections.
+    /// Edge radius between the sections (boxes).
     pub section_edge_radius: f64,
     /// Edge radius between cells.
     pub cell_edge_radius: f64,
@@ -36,6 +53,34 @@ pub struct GameboardViewSettings{
     pub invalid_cell_background_color : Color,
     /// Readonly cell background color.
     pub readonly_cell_background_color : Color,
+    /// Font size of the information line below the board.
+    pub info_font_size : u32,
+    /// Pencil mark color
+    pub notes_color : Color,
+    /// Font size of pencil marks on the classic board. Scaled with the cells
+    /// on other board sizes.
+    pub notes_font_size : u32,
+    /// Background color of the region a hint points to.
+    pub hint_region_color : Color,
+    /// Background color of the pattern cells of a hint.
+    pub hint_cell_color : Color,
+    /// Color of digits placed by a hint.
+    pub hint_placement_color : Color,
+    /// Color of candidates removed by a hint.
+    pub hint_elimination_color : Color,
+    /// Background color of entries which differ from the answer.
+    pub mistake_cell_background_color : Color,
+    /// Background color of the pause, completion and statistics screens.
+    pub overlay_background_color : Color,
+    /// Background color of the diagonals of Sudoku X boards.
+    pub diagonal_background_color : Color,
+    /// Dashed outline color of Killer cages.
+    pub cage_edge_color : Color,
+    /// Dashed outline radius of Killer cages.
+    pub cage_edge_radius : f64,
+    /// Font size of the cage sums on the classic board. Scaled with the
+    /// cells on other board sizes.
+    pub cage_font_size : u32,
 }
 
 impl GameboardViewSettings{
@@ -56,6 +101,19 @@ impl GameboardViewSettings{
             text_color : [0.0,0.0,0.1,1.0],
             invalid_cell_background_color : [0.5,0.0,0.0,0.5],
             readonly_cell_background_color : [0.25,0.25,0.25,0.5],
+            info_font_size : 20,
+            notes_color : [0.2,0.2,0.5,1.0],
+            notes_font_size : 11,
+            hint_region_color : [1.0,1.0,0.6,1.0],
+            hint_cell_color : [1.0,0.8,0.4,1.0],
+            hint_placement_color : [0.0,0.5,0.0,1.0],
+            hint_elimination_color : [0.8,0.0,0.0,1.0],
+            mistake_cell_background_color : [1.0,0.3,0.3,0.6],
+            overlay_background_color : [0.8,0.8,1.0,0.97],
+            diagonal_background_color : [0.7,0.7,0.95,1.0],
+            cage_edge_color : [0.0,0.0,0.2,1.0],
+            cage_edge_radius : 0.5,
+            cage_font_size : 9,
         }
     }
 }
@@ -84,21 +142,39 @@ impl GameboardView{
     )
         where C : CharacterCache<Texture=G::Texture>
     {
-        use graphics::{Line,Rectangle,Image,Transformed};
+        use graphics::{Line,Rectangle,Image,Text,Transformed};
 
         let ref settings = self.settings;
         let board_rect = [
             settings.position[0], settings.position[1],
             settings.size, settings.size,
         ];
+        let shape = controller.gameboard.shape();
+        let n = shape.size();
+        let cell_size = settings.size / n as f64;
 
         // Draw board background
         Rectangle::new(settings.background_color)
             .draw(board_rect,&c.draw_state,c.transform,g);
 
+        // Draw diagonal backgrounds of Sudoku X
+        if controller.gameboard.has_rule("diagonals"){
+            let diagonal_rect = Rectangle::new(settings.diagonal_background_color);
+            for i in 0..n{
+                for &j in [i,n-1-i].iter(){
+                    let cell_rect = [
+                        settings.position[0] + j as f64 * cell_size,
+                        settings.position[1] + i as f64 * cell_size,
+                        cell_size,
+                        cell_size,
+                    ];
+                    diagonal_rect.draw(cell_rect,&c.draw_state,c.transform,g);
+                }
+            }
+        }
+
         // Draw selected cell background
         if let Some(ind) = controller.selected_cell{
-            let cell_size = settings.size / 9.0;
             let pos = [ind.1 as f64 * cell_size, ind.0 as f64 * cell_size];
             let cell_rect = [
                 settings.position[0] + pos[0],
@@ -111,12 +187,32 @@ impl GameboardView{
                 .draw(cell_rect,&c.draw_state,c.transform,g);
         }
 
+        // Draw hint backgrounds
+        if let Some(ref hint) = controller.hint{
+            let cells = if hint.level == HintLevel::Cells {&hint.step.cells} else {&hint.region};
+            let color = if hint.level == HintLevel::Cells{
+                settings.hint_cell_color
+            }else{
+                settings.hint_region_color
+            };
+
+            for &(i,j) in cells.iter(){
+                let cell_rect = [
+                    settings.position[0] + j as f64 * cell_size,
+                    settings.position[1] + i as f64 * cell_size,
+                    cell_size,
+                    cell_size,
+                ];
+                Rectangle::new(color).draw(cell_rect,&c.draw_state,c.transform,g);
+            }
+        }
+
         let invalid_cell_rect = Rectangle::new(settings.invalid_cell_background_color);
         let readonly_cell_rect = Rectangle::new(settings.readonly_cell_background_color);
         let text_image = Image::new_color(settings.text_color);
-        let cell_size = settings.size / 9.0;
-        for j in 0..9{
-            for i in 0..9{
+        let font_size = (cell_size * 0.77) as u32;
+        for j in 0..n{
+            for i in 0..n{
                 let ind = (j,i);
                 let x_pos = settings.position[0] + i as f64 * cell_size;
                 let y_pos = settings.position[1] + j as f64 * cell_size;
@@ -129,16 +225,34 @@ impl GameboardView{
                     invalid_cell_rect.draw(cell_rect,&c.draw_state,c.transform,g);
                 }
 
+                // Draw background of entries reported as wrong
+                let mistake = match controller.mistakes.mode{
+                    CheckMode::Off => false,
+                    CheckMode::Immediate => controller.gameboard.wrong(ind),
+                    CheckMode::OnDemand => controller.mistakes.is_marked(&controller.gameboard,ind),
+                };
+                if mistake{
+                    let cell_rect = [x_pos,y_pos,cell_size,cell_size];
+                    Rectangle::new(settings.mistake_cell_background_color)
+                        .draw(cell_rect,&c.draw_state,c.transform,g);
+                }
+
                 // Draw readonly cell background
                 if readonly{
                     let cell_rect = [x_pos,y_pos,cell_size,cell_size];
                     readonly_cell_rect.draw(cell_rect,&c.draw_state,c.transform,g);
                 }
 
+                // Draw pencil marks of empty cells.
+                if controller.gameboard.get(ind) == 0{
+                    self.draw_notes(controller,ind,[x_pos,y_pos],glyphs,c,g);
+                }
+
                 // Draw characters.
                 if let Some(ch) = controller.gameboard.char(ind){
-                    let pos = [ x_pos + 15.0 , y_pos + 34.0 ];
-                    if let Ok(character) = glyphs.character(34,ch){
+                    let width = glyphs.width(font_size,&ch.to_string()).unwrap_or(0.0);
+                    let pos = [ x_pos + (cell_size - width) / 2.0 , y_pos + cell_size * 0.77 ];
+                    if let Ok(character) = glyphs.character(font_size,ch){
                         let ch_x = pos[0] + character.left();
                         let ch_y = pos[1] - character.top();
                         text_image.draw(
@@ -155,25 +269,323 @@ impl GameboardView{
         let cell_edge = Line::new(settings.cell_edge_color,settings.cell_edge_radius);
         let section_edge = Line::new(settings.section_edge_color, settings.section_edge_radius);
 
-        for i in 0..9{
-            let x = settings.position[0] + i as f64 / 9.0 * settings.size;
-            let y = settings.position[1] + i as f64 / 9.0 * settings.size;
+        for i in 0..n{
+            let x = settings.position[0] + i as f64 * cell_size;
+            let y = settings.position[1] + i as f64 * cell_size;
             let x2 = settings.position[0] + settings.size;
             let y2 = settings.position[1] + settings.size;
 
             let vline = [x, settings.position[1], x, y2];
             let hline = [settings.position[0], y , x2, y];
 
-            if i%3 == 0{
-                section_edge.draw(vline,&c.draw_state,c.transform, g);
-                section_edge.draw(hline,&c.draw_state,c.transform,g);
-            }else{
-                cell_edge.draw(vline,&c.draw_state,c.transform, g);
-                cell_edge.draw(hline,&c.draw_state,c.transform,g);
+            cell_edge.draw(vline,&c.draw_state,c.transform, g);
+            cell_edge.draw(hline,&c.draw_state,c.transform,g);
+        }
+
+        // section edges follow the boxes, or the regions of a Jigsaw board
+        let gameboard = &controller.gameboard;
+        for (i,j) in shape.cells(){
+            let x = settings.position[0] + j as f64 * cell_size;
+            let y = settings.position[1] + i as f64 * cell_size;
+
+            if j > 0 && gameboard.region_of((i,j)) != gameboard.region_of((i,j-1)){
+                section_edge.draw([x,y,x,y + cell_size],&c.draw_state,c.transform,g);
+            }
+            if i > 0 && gameboard.region_of((i,j)) != gameboard.region_of((i-1,j)){
+                section_edge.draw([x,y,x + cell_size,y],&c.draw_state,c.transform,g);
             }
         }
 
         Rectangle::new_border(settings.board_edge_color, settings.board_edge_radius)
             .draw(board_rect, &c.draw_state, c.transform , g);
+
+        // Draw cages
+        if !controller.gameboard.cages().is_empty(){
+            self.draw_cages(controller,glyphs,c,g);
+        }
+
+        // Draw digits placed and candidates removed by the hint
+        if let Some(ref hint) = controller.hint{
+            if hint.level == HintLevel::Cells{
+                let placements = hint.step.placements.iter()
+                    .map(|&(p,d)| (p,d,settings.hint_placement_color));
+                let eliminations = hint.step.eliminations.iter()
+                    .map(|&(p,d)| (p,d,settings.hint_elimination_color));
+
+                for (p,d,color) in placements.chain(eliminations){
+                    let x = settings.position[0] + p.1 as f64 * cell_size;
+                    let y = settings.position[1] + p.0 as f64 * cell_size;
+                    self.draw_mark(controller.gameboard.shape(),Mark{d,pos : [x,y],color},glyphs,c,g);
+                }
+            }
+        }
+
+        // Draw difficulty
+        let mut info = format!("Difficulty: {}  Target: {}",controller.rating,controller.target);
+        if controller.shape.size() != SIZE || n != SIZE{
+            info.push_str(&format!(" {}x{}",controller.shape.size(),controller.shape.size()));
+        }
+        if controller.diagonals{
+            info.push_str(" X");
+        }
+        if controller.killer{
+            info.push_str(" Killer");
+        }
+        if controller.jigsaw{
+            info.push_str(" Jigsaw");
+        }
+        if controller.notes_mode{
+            info.push_str("  [Notes]");
+        }
+        if controller.gameboard.auto_candidates(){
+            info.push_str("  [Auto]");
+        }
+        if controller.mistakes.mode != CheckMode::Off || controller.mistakes.max_mistakes.is_some(){
+            info.push_str(&format!("  Mistakes: {}",controller.mistakes.mistakes));
+            if let Some(max) = controller.mistakes.max_mistakes{
+                info.push_str(&format!("/{}",max));
+            }
+        }
+        if let Some((target,ref err)) = controller.missed_target{
+            info = format!("Target {} missed ({}), Difficulty: {}",target,err,controller.rating);
+        }
+        if let Some(ref hint) = controller.hint{
+            if hint.level >= HintLevel::Technique{
+                info = format!("Hint: {}",hint.step.technique);
+            }
+        }
+        if controller.mistakes.game_over(){
+            info = "Game over - press G for a new quiz".to_string();
+        }
+        let info_pos = [
+            settings.position[0],
+            settings.position[1] + settings.size + settings.info_font_size as f64 + 10.0,
+        ];
+        Text::new_color(settings.text_color,settings.info_font_size)
+            .draw(&info,glyphs,&c.draw_state,c.transform.trans(info_pos[0],info_pos[1]),g)
+            .ok();
+
+        // Draw timer
+        let mut time = format!("Time: {}",format_duration(controller.timer.elapsed()));
+        if !controller.timer.is_running() && !controller.is_over(){
+            time.push_str("  [Paused]");
+        }
+        Text::new_color(settings.text_color,settings.info_font_size)
+            .draw(&time,glyphs,&c.draw_state,
+                c.transform.trans(info_pos[0],info_pos[1] + settings.info_font_size as f64 + 6.0),g)
+            .ok();
+
+        // Draw screens covering the board
+        let lines = if controller.show_stats{
+            stats_lines(controller)
+        }else if controller.completed{
+            completion_lines(controller)
+        }else if !controller.timer.is_running() && !controller.is_over(){
+            vec!["Paused".to_string(),"".to_string(),"Press P to continue".to_string()]
+        }else{
+            vec![]
+        };
+        if !lines.is_empty(){
+            Rectangle::new(settings.overlay_background_color)
+                .draw(board_rect,&c.draw_state,c.transform,g);
+
+            let text = Text::new_color(settings.text_color,settings.info_font_size);
+            let line_height = settings.info_font_size as f64 * 1.4;
+            for (n,line) in lines.iter().enumerate(){
+                let x = settings.position[0] + 20.0;
+                let y = settings.position[1] + 40.0 + n as f64 * line_height;
+                text.draw(line,glyphs,&c.draw_state,c.transform.trans(x,y),g).ok();
+            }
+        }
+    }
+
+    /// Draw the pencil marks of a cell whose upper left corner is `pos`.
+    /// Corner marks (or the candidates in auto-candidate mode) are a small
+    /// digit grid shaped like a box, centre marks a row in the middle.
+    fn draw_notes<G:Graphics,C>(
+        &self,
+        controller: &GameboardController,
+        ind: (usize,usize),
+        pos: [f64;2],
+        glyphs: &mut C,
+        c: &Context,
+        g: &mut G
+    )
+        where C : CharacterCache<Texture=G::Texture>
+    {
+        use graphics::{Text,Transformed};
+
+        let settings = &self.settings;
+        let shape = controller.gameboard.shape();
+        let cell_size = settings.size / shape.size() as f64;
+        let font_size = self.notes_font_size(shape.size());
+        let text = Text::new_color(settings.notes_color,font_size);
+
+        let kind = if controller.gameboard.auto_candidates() {NoteKind::Auto} else {NoteKind::Corner};
+        let corner = controller.gameboard.notes(ind,kind);
+        for d in shape.digits().filter(|d| corner & (1 << d) != 0){
+            self.draw_mark(shape,Mark{d,pos,color : settings.notes_color},glyphs,c,g);
+        }
+
+        let centre = controller.gameboard.notes(ind,NoteKind::Centre);
+        let digits = shape.digits()
+            .filter(|d| centre & (1 << d) != 0)
+            .filter_map(shape::symbol)
+            .collect::<String>();
+        if !digits.is_empty(){
+            let width = glyphs.width(font_size,&digits).unwrap_or(0.0);
+            let x = pos[0] + (cell_size - width) / 2.0;
+            let y = pos[1] + (cell_size + font_size as f64 * 0.7) / 2.0;
+            text.draw(&digits,glyphs,&c.draw_state,c.transform.trans(x,y),g).ok();
+        }
     }
+
+    /// Draw a mark at its place of the small digit grid of its cell
+    /// on a board of `shape`.
+    fn draw_mark<G:Graphics,C>(
+        &self,
+        shape: Shape,
+        mark: Mark,
+        glyphs: &mut C,
+        c: &Context,
+        g: &mut G
+    )
+        where C : CharacterCache<Texture=G::Texture>
+    {
+        use graphics::{Text,Transformed};
+
+        let Mark{d,pos,color} = mark;
+        let (w,h) = (shape.box_width,shape.box_height);
+        let cell_size = self.settings.size / shape.size() as f64;
+        let (row,col) = ((d as usize - 1) / w,(d as usize - 1) % w);
+        let x = pos[0] + (col as f64 + 0.3) * cell_size / w as f64;
+        let y = pos[1] + (row as f64 + 0.85) * cell_size / h as f64;
+        let symbol = shape::symbol(d).map_or(String::new(),|ch| ch.to_string());
+
+        Text::new_color(color,self.notes_font_size(shape.size()))
+            .draw(&symbol,glyphs,&c.draw_state,c.transform.trans(x,y),g)
+            .ok();
+    }
+
+    /// Font size of pencil marks on a board with `size` rows
+    fn notes_font_size(&self,size: usize) -> u32{
+        (self.settings.notes_font_size as usize * SIZE / size).max(6) as u32
+    }
+
+    /// Draw the cages of a Killer board as dashed outlines inside the cells,
+    /// with the sum in the top left corner of every cage.
+    fn draw_cages<G:Graphics,C>(
+        &self,
+        controller: &GameboardController,
+        glyphs: &mut C,
+        c: &Context,
+        g: &mut G
+    )
+        where C : CharacterCache<Texture=G::Texture>
+    {
+        use graphics::{Line,Rectangle,Text,Transformed};
+
+        let settings = &self.settings;
+        let n = controller.gameboard.size();
+        let cell_size = settings.size / n as f64;
+        let inset = cell_size * 0.08;
+        let line = Line::new(settings.cage_edge_color,settings.cage_edge_radius);
+        let font_size = (settings.cage_font_size as usize * SIZE / n).max(6) as u32;
+
+        for cage in controller.gameboard.cages().iter(){
+            let inside = |i: isize,j: isize| i >= 0 && j >= 0 && cage.cells.contains(&(i as usize,j as usize));
+
+            for &(i,j) in cage.cells.iter(){
+                let (i,j) = (i as isize,j as isize);
+                let x = settings.position[0] + j as f64 * cell_size;
+                let y = settings.position[1] + i as f64 * cell_size;
+                // ends of a side reach the cell edge where the cage goes on
+                let left = if inside(i,j-1) {x} else {x + inset};
+                let right = if inside(i,j+1) {x + cell_size} else {x + cell_size - inset};
+                let top = if inside(i-1,j) {y} else {y + inset};
+                let bottom = if inside(i+1,j) {y + cell_size} else {y + cell_size - inset};
+
+                if !inside(i-1,j){
+                    draw_dashed(&line,[left,top,right,top],inset,c,g);
+                }
+                if !inside(i+1,j){
+                    draw_dashed(&line,[left,bottom,right,bottom],inset,c,g);
+                }
+                if !inside(i,j-1){
+                    draw_dashed(&line,[left,top,left,bottom],inset,c,g);
+                }
+                if !inside(i,j+1){
+                    draw_dashed(&line,[right,top,right,bottom],inset,c,g);
+                }
+            }
+
+            // sum on a background patch, so that it stays readable over the outline
+            if let Some((i,j)) = cage.corner(){
+                let label = cage.sum.to_string();
+                let width = glyphs.width(font_size,&label).unwrap_or(0.0);
+                let x = settings.position[0] + j as f64 * cell_size + inset * 0.5;
+                let y = settings.position[1] + i as f64 * cell_size + inset * 0.5;
+                Rectangle::new(settings.background_color)
+                    .draw([x,y,width + 2.0,font_size as f64 + 2.0],&c.draw_state,c.transform,g);
+                Text::new_color(settings.text_color,font_size)
+                    .draw(&label,glyphs,&c.draw_state,c.transform.trans(x + 1.0,y + font_size as f64),g)
+                    .ok();
+            }
+        }
+    }
+}
+
+/// Draw `line` along `coords` (`[x1,y1,x2,y2]`) as dashes of length `dash`
+fn draw_dashed<G:Graphics>(line: &graphics::Line,coords: [f64;4],dash: f64,c: &Context,g: &mut G){
+    let [x1,y1,x2,y2] = coords;
+    let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+    let steps = (length / dash).ceil() as usize;
+
+    // every other piece is a gap
+    for k in (0..steps).step_by(2){
+        let (t1,t2) = (k as f64 / steps as f64,((k + 1) as f64 / steps as f64).min(1.0));
+        line.draw(
+            [x1 + (x2 - x1) * t1,y1 + (y2 - y1) * t1,x1 + (x2 - x1) * t2,y1 + (y2 - y1) * t2],
+            &c.draw_state,c.transform,g);
+    }
+}
+
+/// Lines of the completion screen
+fn completion_lines(controller: &GameboardController) -> Vec<String>{
+    let d = controller.rating.difficulty;
+    let record = controller.stats.level(d);
+    let mut lines = vec![
+        "Solved!".to_string(),
+        "".to_string(),
+        format!("{} in {}",d,format_duration(controller.timer.elapsed())),
+    ];
+
+    if controller.new_best{
+        lines.push("New best time!".to_string());
+    }else if let Some(best) = record.best{
+        lines.push(format!("Best time: {}",format_duration(best)));
+    }
+    lines.push(format!("Streak: {}",controller.stats.all.streak));
+    lines.push("".to_string());
+    lines.push("Press G for a new quiz, T for statistics".to_string());
+
+    lines
+}
+
+/// Lines of the statistics screen
+fn stats_lines(controller: &GameboardController) -> Vec<String>{
+    let line = |name: &str,r: &Record| format!("{:<7} {}/{} won  best {}  avg {}",
+        name,r.won,r.played,
+        r.best.map_or("-".to_string(),format_duration),
+        r.average().map_or("-".to_string(),format_duration));
+
+    let stats = &controller.stats;
+    let mut lines = vec!["Statistics".to_string(),"".to_string()];
+    lines.extend(Difficulty::ALL.iter().map(|&d| line(d.name(),stats.level(d))));
+    lines.push(line("All",&stats.all));
+    lines.push(format!("Streak: {}  (best {})",stats.all.streak,stats.all.best_streak));
+    lines.push("".to_string());
+    lines.push("Press T to go back".to_string());
+
+    lines
 }
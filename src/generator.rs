@@ -1,61 +1,760 @@
-use crate::gameboard::{SIZE,Gameboard};
+//! Sudoku quiz generator.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::{Duration,Instant};
+
+use crate::gameboard::Gameboard;
+#[cfg(test)]
+use crate::gameboard::SIZE;
+use crate::killer::Cage;
+use crate::shape::{Grid,Pos,Shape};
+use crate::rating::Difficulty;
+use crate::solver::{Solver,SudokuSolver,shuffle};
 use rand::prelude::*;
+use rand_chacha::ChaChaRng;
+
+/// Largest cage of Killer quizzes
+const MAX_CAGE : usize = 4;
 
 /// Sudoku generator
+///
+/// Every quiz is built from its own `u64` seed, so the same seed always
+/// yields the same quiz. On a Killer board (one with a `cages` rule) the
+/// quiz is a new cage layout without givens.
 pub struct Generator{
+    /// Solver backend used to fill the board and check uniqueness.
+    solver : Box<dyn SudokuSolver>,
+    /// Draws the seed of every quiz.
+    rng : ChaChaRng,
+    /// Seed of the last generated quiz.
+    last_seed : Option<u64>,
+    /// Givens and symmetry of the quizzes.
+    options : GeneratorOptions,
+}
+
+/// Number of attempts of `generate` to satisfy `max_givens`.
+const DEFAULT_ATTEMPTS : usize = 100;
+
+/// Symmetry of the givens
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Symmetry{
+    /// No symmetry.
+    None,
+    /// Unchanged by a half turn.
+    Rotational180,
+    /// Unchanged by a quarter turn.
+    Rotational90,
+    /// Mirrored across the horizontal axis (top and bottom rows swap).
+    Horizontal,
+    /// Mirrored across the vertical axis (left and right columns swap).
+    Vertical,
+    /// Mirrored across the main diagonal.
+    Diagonal,
+}
+
+impl Symmetry{
+    /// Cells of a board with `size` rows which must be removed together
+    /// with `pos`, including `pos`.
+    pub fn orbit(self,size: usize,pos: (usize,usize))->Vec<(usize,usize)>{
+        let (i,j) = pos;
+        let (ri,rj) = (size - 1 - i,size - 1 - j);
+
+        let mut v = match self{
+            Symmetry::None => vec![(i,j)],
+            Symmetry::Rotational180 => vec![(i,j),(ri,rj)],
+            Symmetry::Rotational90 => vec![(i,j),(j,ri),(ri,rj),(rj,i)],
+            Symmetry::Horizontal => vec![(i,j),(ri,j)],
+            Symmetry::Vertical => vec![(i,j),(i,rj)],
+            Symmetry::Diagonal => vec![(i,j),(j,i)],
+        };
+        v.sort();
+        v.dedup();
+
+        v
+    }
+
+    /// All orbits in random order
+    fn shuffled_orbits<R: Rng>(self,size: usize,rng: &mut R)->Vec<Vec<(usize,usize)>>{
+        let mut cells = (0..size*size)
+            .map(|n| (n%size,n/size))
+            .collect::<Vec<(usize,usize)>>();
+        shuffle(rng,&mut cells);
+
+        let mut seen = vec![vec![false;size];size];
+        let mut orbits = vec![];
+        for pos in cells{
+            if !seen[pos.0][pos.1]{
+                let orbit = self.orbit(size,pos);
+                for &(i,j) in orbit.iter(){
+                    seen[i][j] = true;
+                }
+                orbits.push(orbit);
+            }
+        }
+
+        orbits
+    }
+}
+
+impl FromStr for Symmetry{
+    type Err = String;
+
+    /// Parse `none`, `rot180`, `rot90`, `horizontal`, `vertical` or `diagonal`
+    fn from_str(name: &str) -> Result<Symmetry,String>{
+        match name.to_ascii_lowercase().as_str(){
+            "none" => Ok(Symmetry::None),
+            "rot180" | "rotational180" => Ok(Symmetry::Rotational180),
+            "rot90" | "rotational90" => Ok(Symmetry::Rotational90),
+            "horizontal" => Ok(Symmetry::Horizontal),
+            "vertical" => Ok(Symmetry::Vertical),
+            "diagonal" => Ok(Symmetry::Diagonal),
+            _ => Err(format!("unknown symmetry {:?}",name)),
+        }
+    }
+}
+
+/// Options of Sudoku generator
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct GeneratorOptions{
+    /// Cells are never dug below this number of givens.
+    pub min_givens : usize,
+    /// Quizzes with more givens are rejected.
+    pub max_givens : usize,
+    /// Symmetry of the givens.
+    pub symmetry : Symmetry,
+    /// Remove every given which isn't needed for a unique answer.
+    ///
+    /// The quiz is dug as far as possible and then each remaining given is
+    /// tried on its own, so `min_givens` and the symmetry of that last pass
+    /// are not kept.
+    pub minimal : bool,
+}
+
+impl Default for GeneratorOptions{
+    fn default() -> GeneratorOptions{
+        GeneratorOptions::for_shape(Shape::CLASSIC)
+    }
+}
+
+impl GeneratorOptions{
+    /// Default options for boards of `shape`. The numbers of givens scale
+    /// with the number of cells, e.g. 17 and 36 givens on the classic board.
+    ///
+    /// Boards larger than 16x16 keep more than half of the cells, because
+    /// checking uniqueness of sparser boards takes too long.
+    pub fn for_shape(shape: Shape) -> GeneratorOptions{
+        let cells = shape.size() * shape.size();
+        let (min_givens,max_givens) = if shape.size() > 16{
+            (cells/2,cells*9/16)
+        }else{
+            (cells*17/81,cells - cells*9/16)
+        };
+
+        GeneratorOptions{
+            min_givens,
+            max_givens,
+            symmetry : Symmetry::None,
+            minimal : false,
+        }
+    }
+
+    /// Check that the options can be met, i.e. `min_givens <= max_givens`
+    pub fn validate(&self) -> Result<(),GenerateError>{
+        if self.min_givens > self.max_givens{
+            return Err(GenerateError::InvalidGivens{
+                min_givens : self.min_givens,
+                max_givens : self.max_givens,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits of the search for a quiz of a requested difficulty
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Budget{
+    /// Maximum number of generated quizzes
+    pub max_attempts : usize,
+    /// Maximum wall-clock time. `None` is unlimited.
+    pub max_duration : Option<Duration>,
+}
+
+impl Default for Budget{
+    fn default() -> Budget{
+        Budget{
+            max_attempts : 100,
+            max_duration : Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// Error of Sudoku generator
+#[derive(Clone,Debug,PartialEq)]
+pub enum GenerateError{
+    /// No quiz of the requested givens and difficulty was found within the budget.
+    BudgetExhausted{
+        /// Number of generated quizzes
+        attempts : usize,
+        /// Time spent
+        elapsed : Duration,
+    },
+    /// The options ask for more than `max_givens` givens.
+    InvalidGivens{
+        /// Requested minimum
+        min_givens : usize,
+        /// Requested maximum
+        max_givens : usize,
+    },
+}
+
+impl fmt::Display for GenerateError{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            GenerateError::BudgetExhausted{attempts,elapsed} =>
+                write!(f,"no quiz matching the options after {} attempts ({:.1}s)",
+                    attempts,elapsed.as_secs_f64()),
+            GenerateError::InvalidGivens{min_givens,max_givens} =>
+                write!(f,"at least {} givens can't be at most {}",min_givens,max_givens),
+        }
+    }
+}
+
+impl Error for GenerateError{}
+
+impl Default for Generator{
+    fn default() -> Generator{
+        Generator::new()
+    }
 }
 
 impl Generator{
     /// Create new Generator
     pub fn new()->Generator{
+        Generator::with_solver(Box::new(Solver::new()))
+    }
+
+    /// Create new Generator whose sequence of quizzes is reproducible from `seed`
+    pub fn from_seed(seed: u64)->Generator{
+        let mut generator = Generator::new();
+        generator.rng = ChaChaRng::seed_from_u64(seed);
+
+        generator
+    }
+
+    /// Create new Generator seeded by another random number generator
+    pub fn from_rng<R: RngCore>(rng: &mut R)->Generator{
+        let mut generator = Generator::new();
+        generator.rng = ChaChaRng::from_seed(rng.gen());
+
+        generator
+    }
+
+    /// Create new Generator using a specific solver backend
+    pub fn with_solver(solver: Box<dyn SudokuSolver>)->Generator{
         Generator{
+            solver,
+            rng : ChaChaRng::from_entropy(),
+            last_seed : None,
+            options : GeneratorOptions::default(),
         }
     }
 
-    /// Create new Sudoku quiz
-    pub fn generate(&mut self,gb:&mut Gameboard){
-       gb.reset();
-       gb.solve();
+    /// Seed of the last generated quiz
+    pub fn last_seed(&self)->Option<u64>{
+        self.last_seed
+    }
+
+    /// Current options
+    pub fn options(&self)->&GeneratorOptions{
+        &self.options
+    }
+
+    /// Change options used by the following quizzes.
+    /// Invalid options are rejected and the current ones kept.
+    pub fn set_options(&mut self,options: GeneratorOptions)->Result<(),GenerateError>{
+        options.validate()?;
+        self.options = options;
+
+        Ok(())
+    }
+
+    /// Create new Sudoku quiz and return its seed
+    ///
+    /// On error `gb` holds the last attempt, which has more than `max_givens` givens.
+    pub fn generate(&mut self,gb:&mut Gameboard)->Result<u64,GenerateError>{
+        let seed = self.rng.next_u64();
+        self.generate_from_seed(gb,seed)?;
+
+        Ok(seed)
+    }
+
+    /// Create the Sudoku quiz identified by `seed`
+    ///
+    /// On error `gb` holds the last attempt, which has more than `max_givens` givens.
+    pub fn generate_from_seed(&mut self,gb:&mut Gameboard,seed: u64)->Result<(),GenerateError>{
+        let budget = Budget{
+            max_attempts : DEFAULT_ATTEMPTS,
+            max_duration : None,
+        };
+
+        self.search(gb,seed,None,&budget)
+    }
+
+    /// Create new Sudoku quiz whose difficulty is within `range` and return its seed.
+    ///
+    /// On error `gb` holds the last attempt, which is a valid quiz outside `range`.
+    pub fn generate_with_difficulty(&mut self,gb:&mut Gameboard,range: RangeInclusive<Difficulty>,budget: &Budget)
+        ->Result<u64,GenerateError>
+    {
+        let seed = self.rng.next_u64();
+        self.generate_with_difficulty_from_seed(gb,seed,range,budget)?;
+
+        Ok(seed)
+    }
 
-       self.make_hole(gb,SIZE*SIZE*9/16);
-       self.set_readonly(gb);
+    /// Create the Sudoku quiz identified by `seed` whose difficulty is within `range`.
+    ///
+    /// The same seed and range always yield the same quiz unless the time
+    /// limit of `budget` runs out.
+    pub fn generate_with_difficulty_from_seed(&mut self,gb:&mut Gameboard,seed: u64,range: RangeInclusive<Difficulty>,budget: &Budget)
+        ->Result<(),GenerateError>
+    {
+        self.search(gb,seed,Some(&range),budget)
+    }
+
+    /// Generate quizzes until one satisfies the options and `range`
+    fn search(&mut self,gb:&mut Gameboard,seed: u64,range: Option<&RangeInclusive<Difficulty>>,budget: &Budget)
+        ->Result<(),GenerateError>
+    {
+        let start = Instant::now();
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        let killer = gb.has_rule("cages");
+
+        for attempt in 0..budget.max_attempts{
+            if budget.max_duration.is_some_and(|d| start.elapsed() >= d){
+                return Err(GenerateError::BudgetExhausted{
+                    attempts : attempt,
+                    elapsed : start.elapsed(),
+                });
+            }
+
+            let mut attempt_rng = ChaChaRng::seed_from_u64(rng.next_u64());
+            self.solver.reseed(attempt_rng.next_u64());
+
+            gb.reset();
+            if killer{
+                gb.set_cages(vec![]);
+            }
+            gb.solve_with(&mut *self.solver);
+            let solution = gb.copy_cells();
+
+            if killer{
+                self.make_cages(gb,&mut attempt_rng);
+            }else{
+                self.make_hole(gb,&mut attempt_rng,range);
+            }
+            self.set_readonly(gb);
+            gb.set_seed(Some(seed));
+            gb.set_solution(Some(solution));
+            self.last_seed = Some(seed);
+
+            let in_range = range.is_none_or(|r| r.contains(&gb.rate().difficulty));
+            if count_givens(gb) <= self.options.max_givens && in_range{
+                return Ok(());
+            }
+        }
+
+        Err(GenerateError::BudgetExhausted{
+            attempts : budget.max_attempts,
+            elapsed : start.elapsed(),
+        })
     }
 
     /// Delete several cells
-    fn make_hole(&mut self,gb:&mut Gameboard,n_max_hole: usize){
-        let mut cnt = 0;
-        let mut v = (0..SIZE*SIZE)
-            .map(|n| (n%SIZE,n/SIZE))
+    ///
+    /// Cells are removed orbit by orbit of the symmetry, never going below
+    /// `min_givens`. Digging stops once there are at most `max_givens` givens.
+    /// With a target `range`, removals which make the quiz harder than the
+    /// range are undone, and digging goes on until the quiz is at least as
+    /// hard as the range.
+    fn make_hole(&mut self,gb:&mut Gameboard,rng: &mut ChaChaRng,range: Option<&RangeInclusive<Difficulty>>){
+        let minimal = self.options.minimal;
+        let mut givens = count_givens(gb);
+        let mut difficulty = Difficulty::Easy;
+
+        for orbit in self.options.symmetry.shuffled_orbits(gb.size(),rng){
+            let hard_enough = range.is_none_or(|r| difficulty >= *r.start());
+            if !minimal && givens <= self.options.max_givens && hard_enough{
+                break;
+            }
+
+            let original = orbit.iter()
+                .map(|&pos| (pos,gb.get(pos)))
+                .filter(|&(_,d)| d != 0)
+                .collect::<Vec<((usize,usize),u8)>>();
+            if original.is_empty() || (!minimal && givens < self.options.min_givens + original.len()){
+                continue;
+            }
+
+            // remove digits
+            for &(pos,_) in original.iter(){
+                gb.set(pos,0);
+            }
+
+            if !gb.unique_with(&mut *self.solver){
+                // undo
+                restore(gb,&original);
+                continue;
+            }
+
+            if let Some(range) = range{
+                let d = gb.rate().difficulty;
+                if d > *range.end(){
+                    // too hard, undo
+                    restore(gb,&original);
+                    continue;
+                }
+                difficulty = d;
+            }
+
+            // count removed digits
+            givens -= original.len();
+        }
+
+        if minimal{
+            self.make_minimal(gb,rng);
+        }
+    }
+
+    /// Remove every given whose removal keeps the answer unique.
+    ///
+    /// A single pass is enough: removing givens only adds answers, so a
+    /// given which is needed stays needed.
+    fn make_minimal(&mut self,gb:&mut Gameboard,rng: &mut ChaChaRng){
+        let size = gb.size();
+        let mut v = (0..size*size)
+            .map(|n| (n%size,n/size))
+            .filter(|&pos| gb.get(pos) != 0)
             .collect::<Vec<(usize,usize)>>();
 
-        v.shuffle(&mut rand::thread_rng());
+        shuffle(rng,&mut v);
 
         for pos in v{
             let original = gb.get(pos);
-            // remove digit
             gb.set(pos,0);
 
-            if gb.unique() == false{
-                // undo
+            if !gb.unique_with(&mut *self.solver){
                 gb.set(pos,original);
-            }else{
-                // count removed digit
-                cnt+=1;
             }
+        }
+    }
 
-            if cnt == n_max_hole{
-                break;
+    /// Cover the answer in `gb` with cages and empty every cell
+    ///
+    /// Cages are grown from random cells up to `MAX_CAGE` cells without
+    /// repeating a digit. While the quiz has more than one answer, the cage
+    /// of a cell where two answers differ is split.
+    fn make_cages(&mut self,gb:&mut Gameboard,rng: &mut ChaChaRng){
+        let shape = gb.shape();
+        let answer = gb.copy_cells();
+        let mut cages = grow_cages(shape,&answer,rng);
+        for pos in shape.cells(){
+            gb.set(pos,0);
+        }
+
+        loop{
+            gb.set_cages(cages.iter().map(|cells| Cage::from_answer(&answer,cells.clone())).collect());
+
+            let answers = self.solver.make_answer_list(gb,2);
+            if answers.len() < 2{
+                return;
             }
+
+            // a single cell cage fixes its digit, so the cage is larger
+            let differ = shape.cells()
+                .filter(|&(i,j)| answers[0][i][j] != answers[1][i][j])
+                .collect::<Vec<Pos>>();
+            let p = differ[rng.gen_range(0,differ.len() as u32) as usize];
+            let k = cages.iter().position(|cage| cage.contains(&p)).unwrap();
+            let cage = cages.swap_remove(k);
+            cages.extend(split_cage(shape,cage,p));
         }
     }
 
     /// set readonly value to gameboard
     fn set_readonly(&self,gb:&mut Gameboard){
-        for i in 0..SIZE{
-            for j in 0..SIZE{
+        for i in 0..gb.size(){
+            for j in 0..gb.size(){
                 let f = gb.get((i,j)) != 0;
                 gb.set_readonly((i,j),f);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Number of filled cells
+fn count_givens(gb: &Gameboard)->usize{
+    gb.shape().cells().filter(|&pos| gb.get(pos) != 0).count()
+}
+
+/// Cells next to `p` (left, right, above and below)
+fn neighbours(shape: Shape,p: Pos)->Vec<Pos>{
+    let n = shape.size();
+    let mut v = vec![];
+
+    if p.0 > 0 {v.push((p.0-1,p.1));}
+    if p.0 + 1 < n {v.push((p.0+1,p.1));}
+    if p.1 > 0 {v.push((p.0,p.1-1));}
+    if p.1 + 1 < n {v.push((p.0,p.1+1));}
+
+    v
+}
+
+/// Cover the board with contiguous cages of 2 to `MAX_CAGE` cells (a single
+/// cell if no neighbour fits) without repeating a digit of `answer`
+fn grow_cages(shape: Shape,answer: &Grid,rng: &mut ChaChaRng)->Vec<Vec<Pos>>{
+    let n = shape.size();
+    let mut taken = vec![vec![false;n];n];
+    let mut cages = vec![];
+
+    let mut starts = shape.cells().collect::<Vec<Pos>>();
+    shuffle(rng,&mut starts);
+    for start in starts{
+        if taken[start.0][start.1]{
+            continue;
+        }
+
+        let size = rng.gen_range(2,MAX_CAGE as u32 + 1) as usize;
+        let mut cage = vec![start];
+        taken[start.0][start.1] = true;
+        while cage.len() < size{
+            let mut next = cage.iter()
+                .flat_map(|&p| neighbours(shape,p))
+                .filter(|&(i,j)| !taken[i][j] && cage.iter().all(|&(k,l)| answer[k][l] != answer[i][j]))
+                .collect::<Vec<Pos>>();
+            next.sort();
+            next.dedup();
+            if next.is_empty(){
+                break;
+            }
+
+            let q = next[rng.gen_range(0,next.len() as u32) as usize];
+            taken[q.0][q.1] = true;
+            cage.push(q);
+        }
+        cages.push(cage);
+    }
+
+    cages
+}
+
+/// Split a cage into the half around `p` and the connected parts of the rest
+fn split_cage(shape: Shape,cage: Vec<Pos>,p: Pos)->Vec<Vec<Pos>>{
+    let mut part = vec![p];
+    while part.len() < cage.len().div_ceil(2){
+        let next = part.iter()
+            .flat_map(|&q| neighbours(shape,q))
+            .find(|q| cage.contains(q) && !part.contains(q));
+        match next{
+            Some(q) => part.push(q),
+            None => break,
+        }
+    }
+
+    let mut parts = vec![];
+    let mut rest = cage.into_iter().filter(|q| !part.contains(q)).collect::<Vec<Pos>>();
+    parts.push(part);
+    while let Some(start) = rest.pop(){
+        let mut component = vec![start];
+        let mut k = 0;
+        while k < component.len(){
+            for q in neighbours(shape,component[k]){
+                if let Some(m) = rest.iter().position(|&r| r == q){
+                    component.push(rest.swap_remove(m));
+                }
+            }
+            k += 1;
+        }
+        parts.push(component);
+    }
+
+    parts
+}
+
+/// Put removed digits back
+fn restore(gb: &mut Gameboard,original: &[((usize,usize),u8)]){
+    for &(pos,d) in original.iter(){
+        gb.set(pos,d);
+    }
+}
+
+#[test]
+fn test_generate_seed(){
+    let mut gb1 = Gameboard::new();
+    let mut gb2 = Gameboard::new();
+
+    let seed = Generator::from_seed(1).generate(&mut gb1).unwrap();
+    Generator::new().generate_from_seed(&mut gb2,seed).unwrap();
+
+    assert_eq!(gb1.copy_cells(),gb2.copy_cells());
+    assert_eq!(gb2.seed(),Some(seed));
+    assert!(gb1.unique());
+}
+
+#[test]
+fn test_generate_difficulty(){
+    let mut gb = Gameboard::new();
+    let range = Difficulty::Medium..=Difficulty::Hard;
+    let budget = Budget{ max_attempts : 20, max_duration : None };
+
+    let seed = Generator::from_seed(3).generate_with_difficulty(&mut gb,range.clone(),&budget).unwrap();
+    assert!(range.contains(&gb.rate().difficulty));
+    assert!(gb.unique());
+
+    let mut gb2 = Gameboard::new();
+    Generator::new().generate_with_difficulty_from_seed(&mut gb2,seed,range,&budget).unwrap();
+    assert_eq!(gb.copy_cells(),gb2.copy_cells());
+}
+
+#[test]
+fn test_generate_budget(){
+    let mut gb = Gameboard::new();
+    let budget = Budget{ max_attempts : 0, max_duration : None };
+
+    let result = Generator::new().generate_with_difficulty(&mut gb,Difficulty::Easy..=Difficulty::Evil,&budget);
+    assert!(matches!(result,Err(GenerateError::BudgetExhausted{attempts : 0,..})));
+}
+
+#[test]
+fn test_generate_givens(){
+    let mut generator = Generator::from_seed(4);
+    let options = GeneratorOptions{
+        min_givens : 40,
+        max_givens : 30,
+        ..GeneratorOptions::default()
+    };
+    assert_eq!(generator.set_options(options),Err(GenerateError::InvalidGivens{min_givens : 40,max_givens : 30}));
+    assert_eq!(generator.options(),&GeneratorOptions::default());
+
+    // 4x4 quizzes need at least 4 givens
+    let shape = Shape::new(2,2).unwrap();
+    generator.set_options(GeneratorOptions{
+        min_givens : 0,
+        max_givens : 3,
+        ..GeneratorOptions::for_shape(shape)
+    }).unwrap();
+    let mut gb = Gameboard::with_shape(shape);
+    assert!(matches!(generator.generate(&mut gb),Err(GenerateError::BudgetExhausted{..})));
+    assert!(count_givens(&gb) > 3);
+}
+
+#[test]
+fn test_generate_symmetry(){
+    let mut gb = Gameboard::new();
+    let mut generator = Generator::from_seed(5);
+    generator.set_options(GeneratorOptions{
+        min_givens : 24,
+        max_givens : 30,
+        symmetry : Symmetry::Rotational90,
+        minimal : false,
+    }).unwrap();
+    generator.generate(&mut gb).unwrap();
+
+    let givens = count_givens(&gb);
+    assert!((24..=30).contains(&givens));
+    assert!(gb.unique());
+    for n in 0..SIZE*SIZE{
+        let pos = (n/SIZE,n%SIZE);
+        for q in Symmetry::Rotational90.orbit(SIZE,pos){
+            assert_eq!(gb.get(pos) == 0,gb.get(q) == 0);
+        }
+    }
+}
+
+#[test]
+fn test_symmetry_orbit(){
+    assert_eq!(Symmetry::Rotational180.orbit(SIZE,(4,4)),vec![(4,4)]);
+    assert_eq!(Symmetry::Rotational90.orbit(SIZE,(0,0)),vec![(0,0),(0,8),(8,0),(8,8)]);
+    assert_eq!(Symmetry::Diagonal.orbit(SIZE,(1,2)),vec![(1,2),(2,1)]);
+    assert_eq!(Symmetry::Horizontal.orbit(SIZE,(1,2)),vec![(1,2),(7,2)]);
+    assert_eq!(Symmetry::Horizontal.orbit(6,(1,2)),vec![(1,2),(4,2)]);
+}
+
+#[test]
+fn test_generate_minimal(){
+    let mut gb = Gameboard::new();
+    let mut generator = Generator::from_seed(7);
+    generator.set_options(GeneratorOptions{
+        minimal : true,
+        ..GeneratorOptions::default()
+    }).unwrap();
+    generator.generate(&mut gb).unwrap();
+
+    assert!(gb.is_minimal());
+}
+
+#[test]
+fn test_generate_shapes(){
+    for &size in [4,6,8].iter(){
+        let shape = Shape::from_size(size).unwrap();
+        let mut gb = Gameboard::with_shape(shape);
+        let mut generator = Generator::from_seed(11);
+        generator.set_options(GeneratorOptions::for_shape(shape)).unwrap();
+        generator.generate(&mut gb).unwrap();
+
+        assert!(count_givens(&gb) <= generator.options().max_givens);
+        assert!(gb.unique());
+        assert!(gb.cache_solution());
+        gb.solve();
+        assert!(gb.finished());
+    }
+}
+
+#[test]
+fn test_generate_diagonals(){
+    let mut gb = Gameboard::new();
+    gb.set_rules(crate::constraint::sudoku_x());
+    Generator::from_seed(5).generate(&mut gb).unwrap();
+
+    assert!(gb.has_rule("diagonals"));
+    assert!(gb.unique());
+    // finished boards follow every rule
+    gb.solve();
+    assert!(gb.finished());
+}
+
+#[test]
+fn test_generate_killer(){
+    let mut gb = Gameboard::new();
+    gb.set_cages(vec![]);
+    Generator::from_seed(2).generate(&mut gb).unwrap();
+
+    assert_eq!(count_givens(&gb),0);
+    assert!(gb.cages().iter().all(|cage| cage.cells.len() <= MAX_CAGE));
+    assert_eq!(gb.cages().iter().map(|cage| cage.cells.len()).sum::<usize>(),SIZE*SIZE);
+    assert_eq!(gb.cages().iter().map(|cage| cage.sum).sum::<u32>(),45*SIZE as u32);
+    assert!(gb.unique());
+    gb.solve();
+    assert!(gb.finished());
+}
+
+#[test]
+fn test_generate_jigsaw(){
+    use crate::constraint::Constraint;
+    use crate::jigsaw::Regions;
+
+    let regions = Regions::random(Shape::CLASSIC,&mut ChaChaRng::seed_from_u64(5));
+    let mut gb = Gameboard::new();
+    gb.set_regions(regions.clone());
+    Generator::from_seed(5).generate(&mut gb).unwrap();
+
+    assert_eq!(gb.regions(),Some(&regions));
+    assert!(gb.unique());
+    gb.solve();
+    assert!(gb.finished());
+    assert!(regions.validate(gb.shape(),&gb.copy_cells()));
+}
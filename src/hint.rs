@@ -0,0 +1,170 @@
+//! Graduated hints
+//!
+//! A hint is revealed in stages: first the region to look at, then the
+//! technique, then the exact cells and candidates. Asking once more
+//! applies the step.
+
+use crate::gameboard::{Gameboard,NoteKind};
+#[cfg(test)]
+use crate::gameboard::SIZE;
+use crate::logical_solver::{LogicalSolver,Step,Technique};
+use crate::shape::Pos;
+
+/// How much of a hint is revealed
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum HintLevel{
+    /// The row, column or section to look at
+    Region,
+    /// Name of the technique
+    Technique,
+    /// Cells of the pattern and the digits placed or removed
+    Cells,
+}
+
+/// Next logical step of a game, revealed step by step
+#[derive(Clone,Debug)]
+pub struct Hint{
+    /// The step
+    pub step : Step,
+    /// Revealed level
+    pub level : HintLevel,
+    /// Cells to look at
+    pub region : Vec<Pos>,
+}
+
+impl Hint{
+    /// Find the next logical step of the current cells.
+    ///
+    /// In auto-candidate mode candidates the player removed are taken into
+    /// account. Returns `None` if the board has conflicts or no technique
+    /// applies.
+    pub fn find(gb: &Gameboard) -> Option<Hint>{
+        if gb.invalid(){
+            return None;
+        }
+
+        let mut solver = LogicalSolver::new(gb);
+        if gb.auto_candidates(){
+            for (i,j) in gb.shape().cells(){
+                let removed = solver.candidates((i,j)) & !gb.notes((i,j),NoteKind::Auto);
+                for d in gb.shape().digits().filter(|d| removed & (1 << d) != 0){
+                    solver.eliminate((i,j),d);
+                }
+            }
+        }
+
+        let step = solver.next_step()?;
+        let region = region(gb,&solver,&step);
+
+        Some(Hint{
+            step,
+            level : HintLevel::Region,
+            region,
+        })
+    }
+
+    /// Reveal the next level. Returns `false` if everything is revealed.
+    pub fn reveal(&mut self) -> bool{
+        self.level = match self.level{
+            HintLevel::Region => HintLevel::Technique,
+            HintLevel::Technique => HintLevel::Cells,
+            HintLevel::Cells => return false,
+        };
+
+        true
+    }
+
+    /// Place the digits of the step and remove its candidates.
+    /// Candidates can only be removed in auto-candidate mode, so the mode is
+    /// turned on if the step has eliminations.
+    pub fn apply(&self,gb: &mut Gameboard){
+        if !self.step.eliminations.is_empty() && !gb.auto_candidates(){
+            gb.set_auto_candidates(true);
+        }
+
+        for &(p,d) in self.step.eliminations.iter(){
+            let notes = gb.notes(p,NoteKind::Auto) & !(1 << d);
+            gb.set_notes(p,NoteKind::Auto,notes);
+        }
+        for &(p,d) in self.step.placements.iter(){
+            gb.set(p,d);
+        }
+    }
+}
+
+/// The unit holding the pattern of a step, or the sections (regions) it
+/// spans if there is no such unit
+fn region(gb: &Gameboard,solver: &LogicalSolver,step: &Step) -> Vec<Pos>{
+    let shape = gb.shape();
+    // units which hold every digit, e.g. no cages
+    let mut units = gb.units().into_iter()
+        .filter(|unit| unit.len() == shape.size())
+        .collect::<Vec<Vec<Pos>>>();
+    // prefer sections and other units, then rows and columns
+    units.sort_by_key(|unit| unit.iter().all(|p| p.0 == unit[0].0) || unit.iter().all(|p| p.1 == unit[0].1));
+
+    let unit = units.into_iter()
+        .filter(|unit| step.cells.iter().all(|p| unit.contains(p)))
+        .find(|unit| match (step.technique,step.placements.first()){
+            // the unit where the digit has no other place
+            (Technique::HiddenSingle,Some(&(_,d))) =>
+                unit.iter().filter(|&&p| solver.candidates(p) & (1 << d) != 0).count() == 1,
+            _ => true,
+        });
+
+    match unit{
+        Some(unit) => unit,
+        None => shape.cells()
+            .filter(|p| step.cells.iter().any(|q| gb.region_of(*p) == gb.region_of(*q)))
+            .collect(),
+    }
+}
+
+#[test]
+fn test_hint_levels(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    let mut hint = Hint::find(&gb).unwrap();
+
+    assert_eq!(hint.level,HintLevel::Region);
+    assert_eq!(hint.region.len(),SIZE);
+    assert!(hint.step.cells.iter().all(|p| hint.region.contains(p)));
+
+    assert!(hint.reveal());
+    assert!(hint.reveal());
+    assert_eq!(hint.level,HintLevel::Cells);
+    assert!(!hint.reveal());
+
+    let empty = (0..SIZE*SIZE).filter(|&n| gb.get((n/SIZE,n%SIZE)) == 0).count();
+    hint.apply(&mut gb);
+    let after = (0..SIZE*SIZE).filter(|&n| gb.get((n/SIZE,n%SIZE)) == 0).count();
+    assert_eq!(after,empty - 1);
+}
+
+#[test]
+fn test_hint_eliminations(){
+    // needs more than singles
+    let mut gb : Gameboard =
+        "52...6.........7.13...........4..8..6......5...........418.........3..2...87....."
+        .parse().unwrap();
+
+    // apply hints until the first one which only removes candidates
+    let hint = loop{
+        let hint = Hint::find(&gb).unwrap();
+        if hint.step.placements.is_empty(){
+            break hint;
+        }
+        hint.apply(&mut gb);
+    };
+
+    hint.apply(&mut gb);
+    assert!(gb.auto_candidates());
+    for &(p,d) in hint.step.eliminations.iter(){
+        assert_eq!(gb.notes(p,NoteKind::Auto) & (1 << d),0);
+    }
+
+    // the next hint builds on the removed candidates
+    let next = Hint::find(&gb).unwrap();
+    assert_ne!(next.step,hint.step);
+}
@@ -0,0 +1,236 @@
+//! Undo/redo history of a game
+//!
+//! Every user action is recorded as a `Command` holding the cells it
+//! changed, so it can be reverted and applied again.
+
+use crate::gameboard::{Gameboard,NoteKind};
+use crate::shape::Pos;
+
+/// Change of one cell
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct Change{
+    /// Changed cell
+    pub pos : Pos,
+    /// Digit before the change
+    pub before : u8,
+    /// Digit after the change
+    pub after : u8,
+    /// Pencil marks of every `NoteKind` before the change
+    pub notes_before : [u32;3],
+    /// Pencil marks of every `NoteKind` after the change
+    pub notes_after : [u32;3],
+}
+
+/// Pencil marks of every `NoteKind` of a cell
+fn notes(gb: &Gameboard,pos: Pos) -> [u32;3]{
+    let mut notes = [0;3];
+    for (n,&kind) in notes.iter_mut().zip(NoteKind::ALL.iter()){
+        *n = gb.notes(pos,kind);
+    }
+
+    notes
+}
+
+/// Replace the pencil marks of a cell which differ between `from` and `to`.
+/// Kinds untouched by the change are left alone, so a command recorded while
+/// the auto-candidate mode was off doesn't wipe the candidates.
+fn set_notes(gb: &mut Gameboard,pos: Pos,from: [u32;3],to: [u32;3]){
+    for (n,&kind) in NoteKind::ALL.iter().enumerate(){
+        if from[n] != to[n]{
+            gb.set_notes(pos,kind,to[n]);
+        }
+    }
+}
+
+/// Reversible action on a gameboard
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Command{
+    /// Changed cells
+    pub changes : Vec<Change>,
+}
+
+impl Command{
+    /// Command turning `before` into `after`. Returns `None` if nothing changed.
+    pub fn diff(before: &Gameboard,after: &Gameboard) -> Option<Command>{
+        let changes = after.shape().cells()
+            .map(|pos| Change{
+                pos,
+                before : before.get(pos),
+                after : after.get(pos),
+                notes_before : notes(before,pos),
+                notes_after : notes(after,pos),
+            })
+            .filter(|c| c.before != c.after || c.notes_before != c.notes_after)
+            .collect::<Vec<Change>>();
+
+        if changes.is_empty(){
+            None
+        }else{
+            Some(Command{changes})
+        }
+    }
+
+    /// Apply the command
+    pub fn apply(&self,gb: &mut Gameboard){
+        for change in self.changes.iter(){
+            gb.set_raw(change.pos,change.after);
+            set_notes(gb,change.pos,change.notes_before,change.notes_after);
+        }
+    }
+
+    /// Revert the command
+    pub fn revert(&self,gb: &mut Gameboard){
+        for change in self.changes.iter().rev(){
+            gb.set_raw(change.pos,change.before);
+            set_notes(gb,change.pos,change.notes_after,change.notes_before);
+        }
+    }
+}
+
+/// Undo and redo stacks (unlimited depth)
+#[derive(Clone,Default)]
+pub struct History{
+    /// Commands which can be undone, oldest first
+    undo : Vec<Command>,
+    /// Undone commands which can be redone, oldest undo last
+    redo : Vec<Command>,
+}
+
+impl History{
+    /// Create new empty History
+    pub fn new() -> History{
+        History{
+            undo : vec![],
+            redo : vec![],
+        }
+    }
+
+    /// Record the change from `before` to `after`.
+    /// A new action discards the redo stack.
+    pub fn record(&mut self,before: &Gameboard,after: &Gameboard){
+        if let Some(command) = Command::diff(before,after){
+            self.undo.push(command);
+            self.redo.clear();
+        }
+    }
+
+    /// Revert the last command. Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self,gb: &mut Gameboard) -> bool{
+        match self.undo.pop(){
+            Some(command) => {
+                command.revert(gb);
+                self.redo.push(command);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Apply the last undone command again.
+    /// Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self,gb: &mut Gameboard) -> bool{
+        match self.redo.pop(){
+            Some(command) => {
+                command.apply(gb);
+                self.undo.push(command);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Forget everything, e.g. when a new quiz starts
+    pub fn clear(&mut self){
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// if there is a command to undo then true
+    pub fn can_undo(&self) -> bool{
+        !self.undo.is_empty()
+    }
+
+    /// if there is a command to redo then true
+    pub fn can_redo(&self) -> bool{
+        !self.redo.is_empty()
+    }
+}
+
+#[test]
+fn test_undo_redo(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    let start = gb.clone();
+    let mut history = History::new();
+
+    for &(pos,d) in [((0,0),4),((0,1),8),((0,0),0)].iter(){
+        let before = gb.clone();
+        gb.set(pos,d);
+        history.record(&before,&gb);
+    }
+
+    let before = gb.clone();
+    gb.solve();
+    history.record(&before,&gb);
+    let solved = gb.copy_cells();
+
+    while history.undo(&mut gb){}
+    assert_eq!(gb.copy_cells(),start.copy_cells());
+    assert!(!history.can_undo());
+
+    while history.redo(&mut gb){}
+    assert_eq!(gb.copy_cells(),solved);
+}
+
+#[test]
+fn test_record_clears_redo(){
+    let mut gb = Gameboard::new();
+    let mut history = History::new();
+
+    // setting a readonly cell does nothing and isn't recorded
+    gb.set_readonly((1,1),true);
+    let before = gb.clone();
+    gb.set((1,1),5);
+    history.record(&before,&gb);
+    assert!(!history.can_undo());
+
+    let before = gb.clone();
+    gb.set((0,0),1);
+    history.record(&before,&gb);
+    history.undo(&mut gb);
+    assert!(history.can_redo());
+
+    let before = gb.clone();
+    gb.set((0,1),2);
+    history.record(&before,&gb);
+    assert!(!history.can_redo());
+    assert_eq!(gb.get((0,0)),0);
+
+    // pencil marks are recorded too
+    let before = gb.clone();
+    gb.toggle_note((2,2),NoteKind::Centre,7);
+    history.record(&before,&gb);
+    history.undo(&mut gb);
+    assert_eq!(gb.notes((2,2),NoteKind::Centre),0);
+}
+
+#[test]
+fn test_undo_auto_candidates(){
+    let mut gb = Gameboard::new();
+    let mut history = History::new();
+    gb.set_auto_candidates(true);
+
+    // the user eliminates 5 from (0,1) by hand, then places 5 at (0,0)
+    let before = gb.clone();
+    gb.toggle_note((0,1),NoteKind::Auto,5);
+    history.record(&before,&gb);
+    let before = gb.clone();
+    gb.set((0,0),5);
+    history.record(&before,&gb);
+    assert_eq!(gb.notes((0,2),NoteKind::Auto) & 1 << 5,0);
+
+    history.undo(&mut gb);
+    assert_ne!(gb.notes((0,2),NoteKind::Auto) & 1 << 5,0);
+    assert_eq!(gb.notes((0,1),NoteKind::Auto) & 1 << 5,0);
+}
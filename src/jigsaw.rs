@@ -0,0 +1,303 @@
+//! Jigsaw Sudoku regions
+//!
+//! A Jigsaw board has irregular regions instead of boxes: `size` contiguous
+//! groups of `size` cells, each holding every digit. The regions are given
+//! by a region map, read from a text layout with one symbol per cell:
+//!
+//! ```text
+//! 111222333
+//! 111222333
+//! 111225663
+//! 444525633
+//! 444555666
+//! 744855966
+//! 774858969
+//! 777888999
+//! 777888999
+//! ```
+//!
+//! Cells with the same symbol belong to the same region. Any symbols other
+//! than whitespace may be used; whitespace is ignored, so the layout may
+//! also be written on a single line.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::constraint::Constraint;
+use crate::gameboard::Gameboard;
+use crate::shape::{self,Grid,Pos,Shape};
+use crate::solver::{self,Solver,SudokuSolver};
+
+/// Region map of a Jigsaw board
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Regions{
+    /// Region index of every cell, indexed by row and column
+    map : Vec<Vec<usize>>,
+}
+
+impl Regions{
+    /// Create new Regions from the region index of every cell.
+    ///
+    /// Returns an error unless the map is square, of a supported board size,
+    /// and every region is a contiguous group of as many cells as the board
+    /// has rows.
+    pub fn new(map: Vec<Vec<usize>>) -> Result<Regions,String>{
+        let n = map.len();
+        if Shape::from_size(n).is_none() || map.iter().any(|row| row.len() != n){
+            return Err(format!("unsupported layout size {}x{}",n,map.first().map_or(0,Vec::len)));
+        }
+
+        let regions = Regions{map};
+        for k in 0..n{
+            let cells = regions.cells(k);
+            if cells.len() != n{
+                return Err(format!("region {} has {} cells instead of {}",k + 1,cells.len(),n));
+            }
+            if !regions.contiguous(k){
+                return Err(format!("region {} is not contiguous",k + 1));
+            }
+        }
+
+        Ok(regions)
+    }
+
+    /// The boxes of `shape` as regions
+    pub fn boxes(shape: Shape) -> Regions{
+        let n = shape.size();
+
+        Regions{map : (0..n).map(|i| (0..n).map(|j| shape.box_of((i,j))).collect()).collect()}
+    }
+
+    /// Random layout for `shape` which has an answer.
+    ///
+    /// Starts from the boxes and an answer of the classic board, and trades
+    /// cells of the same digit between neighbouring regions while they stay
+    /// contiguous, so that the answer still fits.
+    pub fn random<R: Rng>(shape: Shape,rng: &mut R) -> Regions{
+        let n = shape.size();
+        let answer = Solver::from_rng(rng).make_answer_list(&Gameboard::with_shape(shape),1).remove(0);
+        let mut regions = Regions::boxes(shape);
+
+        for _ in 0..n*n*n{
+            regions.trade(&answer,rng);
+        }
+
+        regions
+    }
+
+    /// Rows (columns, regions) of the board
+    pub fn size(&self) -> usize{
+        self.map.len()
+    }
+
+    /// Region index of a cell
+    pub fn region_of(&self,p: Pos) -> usize{
+        self.map[p.0][p.1]
+    }
+
+    /// Cells of region `k` in row-major order
+    pub fn cells(&self,k: usize) -> Vec<Pos>{
+        let n = self.size();
+
+        (0..n*n).map(|c| (c/n,c%n))
+            .filter(|&p| self.region_of(p) == k)
+            .collect()
+    }
+
+    /// Cells next to `p` in the same row or column
+    fn neighbours(&self,p: Pos) -> impl Iterator<Item=Pos>{
+        let n = self.size();
+        let (i,j) = p;
+
+        vec![(i.wrapping_sub(1),j),(i + 1,j),(i,j.wrapping_sub(1)),(i,j + 1)].into_iter()
+            .filter(move |&(i,j)| i < n && j < n)
+    }
+
+    /// if the cells of region `k` are connected then true
+    fn contiguous(&self,k: usize) -> bool{
+        let cells = self.cells(k);
+        let mut seen = vec![cells[0]];
+        let mut stack = vec![cells[0]];
+
+        while let Some(p) = stack.pop(){
+            for q in self.neighbours(p){
+                if self.region_of(q) == k && !seen.contains(&q){
+                    seen.push(q);
+                    stack.push(q);
+                }
+            }
+        }
+
+        seen.len() == cells.len()
+    }
+
+    /// Swap a random border cell of a region with a cell of a neighbouring
+    /// region holding the same digit in `answer`, unless a region would
+    /// fall apart
+    fn trade<R: Rng>(&mut self,answer: &Grid,rng: &mut R){
+        let n = self.size();
+        let p = (rng.gen_range(0,n as u32) as usize,rng.gen_range(0,n as u32) as usize);
+        let (a,mut others) = (self.region_of(p),self.neighbours(p).collect::<Vec<Pos>>());
+        solver::shuffle(rng,&mut others);
+        let b = match others.into_iter().map(|q| self.region_of(q)).find(|&b| b != a){
+            Some(b) => b,
+            None => return,
+        };
+
+        // a cell of `b` which can move to `a`
+        let mut candidates = self.cells(b).into_iter()
+            .filter(|&(i,j)| answer[i][j] == answer[p.0][p.1])
+            .filter(|&q| self.neighbours(q).any(|r| r != p && self.region_of(r) == a))
+            .collect::<Vec<Pos>>();
+        solver::shuffle(rng,&mut candidates);
+
+        for q in candidates{
+            self.map[p.0][p.1] = b;
+            self.map[q.0][q.1] = a;
+            if self.contiguous(a) && self.contiguous(b){
+                return;
+            }
+            self.map[p.0][p.1] = a;
+            self.map[q.0][q.1] = b;
+        }
+    }
+}
+
+impl Constraint for Regions{
+    fn name(&self) -> &str{
+        "regions"
+    }
+
+    fn units(&self,_shape: Shape) -> Vec<Vec<Pos>>{
+        (0..self.size()).map(|k| self.cells(k)).collect()
+    }
+}
+
+impl fmt::Display for Regions{
+    /// Write the layout with a line per row and the symbols of the digits
+    /// (`1` for the first region).
+    ///
+    /// The alternate form (`{:#}`) writes a single line.
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        for (i,row) in self.map.iter().enumerate(){
+            if i > 0 && !f.alternate(){
+                writeln!(f)?;
+            }
+            for &k in row.iter(){
+                write!(f,"{}",shape::symbol(k as u8 + 1).unwrap_or('?'))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Regions{
+    type Err = String;
+
+    /// Parse a layout. Regions are numbered in the order they first appear.
+    fn from_str(s: &str) -> Result<Regions,String>{
+        let symbols = s.chars().filter(|ch| !ch.is_whitespace()).collect::<Vec<char>>();
+        let n = (1..=symbols.len()).take_while(|k| k*k <= symbols.len()).last().unwrap_or(0);
+        if n*n != symbols.len() || n == 0{
+            return Err(format!("expected n*n cells but found {}",symbols.len()));
+        }
+
+        let mut names = vec![];
+        let mut map = vec![vec![0;n];n];
+        for (c,ch) in symbols.into_iter().enumerate(){
+            map[c/n][c%n] = match names.iter().position(|&name| name == ch){
+                Some(k) => k,
+                None => {
+                    names.push(ch);
+                    names.len() - 1
+                },
+            };
+        }
+        if names.len() != n{
+            return Err(format!("expected {} regions but found {}",n,names.len()));
+        }
+
+        Regions::new(map)
+    }
+}
+
+#[test]
+fn test_regions_layout(){
+    let layout = "\
+        111222333\n111222333\n111225663\n\
+        444525633\n444555666\n744855966\n\
+        774858969\n777888999\n777888999";
+    let regions : Regions = layout.parse().unwrap();
+
+    assert_eq!(regions.region_of((3,4)),1);
+    assert_eq!(regions.region_of((5,0)),6);
+    assert_eq!(regions.region_of((0,0)),0);
+    assert_eq!(regions.cells(0).len(),9);
+    assert_eq!(regions.to_string().parse::<Regions>(),Ok(regions.clone()));
+    assert_eq!(format!("{:#}",regions).parse::<Regions>(),Ok(regions));
+    assert_eq!("aab\nabb\ncc".parse::<Regions>().err(),Some("expected n*n cells but found 8".to_string()));
+    // region 1 is split in two
+    assert!("1221\n1221\n3344\n3344".parse::<Regions>().is_err());
+    assert!("1122\n1122\n3333\n4444".parse::<Regions>().is_ok());
+    assert!("1112\n1222\n3334\n3344".parse::<Regions>().is_err());
+}
+
+#[test]
+fn test_random_regions(){
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+
+    let shape = Shape::CLASSIC;
+    let regions = Regions::random(shape,&mut ChaChaRng::seed_from_u64(7));
+
+    assert_ne!(regions,Regions::boxes(shape));
+    assert_eq!(Regions::new(regions.map.clone()),Ok(regions));
+}
+
+#[test]
+fn test_region_conflicts(){
+    let mut gb = Gameboard::new();
+    let regions : Regions = "\
+        111222333\n111222333\n111225663\n\
+        444525633\n444555666\n744855966\n\
+        774858969\n777888999\n777888999".parse().unwrap();
+    gb.set_regions(regions);
+    assert!(!gb.has_rule("boxes"));
+
+    // same region, other boxes
+    gb.set((0,3),5);
+    gb.set((3,4),5);
+    assert!(gb.get_invalid((0,3)) && gb.get_invalid((3,4)));
+    gb.set((0,3),0);
+
+    // same box, other regions
+    gb.set((4,3),5);
+    assert!(!gb.invalid());
+    gb.clear_regions();
+    assert!(gb.get_invalid((4,3)) && gb.regions().is_none());
+}
+
+#[test]
+fn test_jigsaw_answers(){
+    use std::collections::HashSet;
+    use crate::dancing_links::DancingLinksSolver;
+
+    let regions : Regions = "\
+        111222333\n111222333\n111225663\n\
+        444525633\n444555666\n744855966\n\
+        774858969\n777888999\n777888999".parse().unwrap();
+    let mut gb : Gameboard =
+        ".......................................31..428.97654.1...54.2..34....87579..24..6"
+        .parse().unwrap();
+    gb.set_regions(regions);
+    let answers = Solver::from_seed(5).make_answer_list(&gb,usize::MAX);
+
+    // every answer exactly once, the same ones as the other backend
+    assert_eq!(answers.len(),978);
+    assert_eq!(answers.iter().collect::<HashSet<&Grid>>().len(),answers.len());
+    assert_eq!(DancingLinksSolver::new().make_answer_list(&gb,usize::MAX).len(),answers.len());
+    assert_eq!(Solver::from_seed(5).make_answer_list(&gb,usize::MAX),answers);
+}
@@ -0,0 +1,199 @@
+//! Killer Sudoku cages
+//!
+//! A cage is a group of cells whose digits add up to the sum of the cage
+//! and which holds every digit at most once. A Killer board keeps its cages
+//! next to the cells (see `Gameboard::set_cages`) and usually has no givens.
+
+use crate::constraint::{self,Constraint};
+use crate::shape::{Grid,Pos,Shape};
+
+/// Cells whose digits add up to `sum`
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Cage{
+    /// Sum of the digits
+    pub sum : u32,
+    /// Cells of the cage
+    pub cells : Vec<Pos>,
+}
+
+impl Cage{
+    /// Create new Cage
+    pub fn new(sum: u32,cells: Vec<Pos>) -> Cage{
+        Cage{sum,cells}
+    }
+
+    /// Cage around `cells` with the sum of their digits in `answer`
+    pub fn from_answer(answer: &Grid,cells: Vec<Pos>) -> Cage{
+        let sum = cells.iter().map(|&(i,j)| answer[i][j] as u32).sum();
+
+        Cage{sum,cells}
+    }
+
+    /// Top left cell, where the sum is shown
+    pub fn corner(&self) -> Option<Pos>{
+        self.cells.iter().cloned().min()
+    }
+
+    /// Digit bit mask of every cell of the cage which is possible under the
+    /// sum, given the candidate bit masks `masks` of the cells. A cell gets
+    /// `0` if no combination of digits fits.
+    pub fn allowed(&self,shape: Shape,masks: &[u32]) -> Vec<u32>{
+        let union = masks.iter().fold(0,|s,&m| s | m);
+        let mut allowed = vec![0;masks.len()];
+        // digits of every combination
+        let mut required = shape.all_digits();
+
+        combinations(shape.size() as u32,union,masks.len(),self.sum,1,0,&mut |combo|{
+            // every cell needs a digit of the combination
+            if masks.iter().all(|&m| m & combo != 0){
+                for (a,&m) in allowed.iter_mut().zip(masks.iter()){
+                    *a |= m & combo;
+                }
+                required &= combo;
+            }
+        });
+
+        // a required digit which fits only one cell goes there
+        for d in shape.digits().filter(|d| required & (1 << d) != 0){
+            let cells = allowed.iter().filter(|&&a| a & (1 << d) != 0).count();
+            if let (1,Some(a)) = (cells,allowed.iter_mut().find(|a| **a & (1 << d) != 0)){
+                *a = 1 << d;
+            }
+        }
+
+        allowed
+    }
+}
+
+/// Rule of the cages of a Killer board
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct Cages{
+    /// All cages
+    pub cages : Vec<Cage>,
+}
+
+impl Cages{
+    /// Create new Cages
+    pub fn new(cages: Vec<Cage>) -> Cages{
+        Cages{cages}
+    }
+}
+
+impl Constraint for Cages{
+    fn name(&self) -> &str{
+        "cages"
+    }
+
+    fn units(&self,_shape: Shape) -> Vec<Vec<Pos>>{
+        self.cages.iter().map(|cage| cage.cells.clone()).collect()
+    }
+
+    /// Repeated digits of a cage, and the digits of a cage which exceed its
+    /// sum (or miss it when the cage is full)
+    fn conflicts(&self,_shape: Shape,cells: &Grid) -> Vec<Pos>{
+        let mut v = vec![];
+
+        for cage in self.cages.iter(){
+            v.extend(constraint::duplicates(cells,&cage.cells));
+
+            let filled = cage.cells.iter()
+                .cloned()
+                .filter(|&(i,j)| cells[i][j] != 0)
+                .collect::<Vec<Pos>>();
+            let sum = filled.iter().map(|&(i,j)| cells[i][j] as u32).sum::<u32>();
+            if sum > cage.sum || (filled.len() == cage.cells.len() && sum != cage.sum){
+                v.extend(filled);
+            }
+        }
+
+        v
+    }
+
+    /// Remove the digits which appear in no combination of the sum
+    fn prune(&self,shape: Shape,candidates: &mut [u32]) -> bool{
+        let n = shape.size();
+
+        for cage in self.cages.iter(){
+            let masks = cage.cells.iter()
+                .map(|&(i,j)| candidates[i*n + j])
+                .collect::<Vec<u32>>();
+
+            for (&(i,j),a) in cage.cells.iter().zip(cage.allowed(shape,&masks)){
+                if a == 0{
+                    return false;
+                }
+                candidates[i*n + j] = a;
+            }
+        }
+
+        true
+    }
+}
+
+/// Call `f` with the bit mask of every set of `k` different digits of
+/// `union`, each at least `from` and at most `n`, adding up to `sum`.
+/// `chosen` holds the digits picked so far.
+fn combinations(n: u32,union: u32,k: usize,sum: u32,from: u32,chosen: u32,f: &mut dyn FnMut(u32)){
+    if k == 0{
+        if sum == 0{
+            f(chosen);
+        }
+        return;
+    }
+
+    // the remaining digits are at most n, n-1, ...
+    let m = k as u32;
+    if n*m < sum + m*(m-1)/2{
+        return;
+    }
+
+    for d in from..=n{
+        // ... and at least d, d+1, ...
+        if d*m + m*(m-1)/2 > sum{
+            break;
+        }
+        if union & (1 << d) != 0{
+            combinations(n,union,k - 1,sum - d,d + 1,chosen | 1 << d,f);
+        }
+    }
+}
+
+#[test]
+fn test_cage_allowed(){
+    let shape = Shape::CLASSIC;
+    let all = shape.all_digits();
+
+    // 3 in two cells is 1+2
+    let cage = Cage::new(3,vec![(0,0),(0,1)]);
+    assert_eq!(cage.allowed(shape,&[all,all]),vec![0b110,0b110]);
+
+    // 10 in two cells with a 3 is 3+7
+    let cage = Cage::new(10,vec![(0,0),(0,1)]);
+    assert_eq!(cage.allowed(shape,&[1 << 3,all & !(1 << 3)]),vec![1 << 3,1 << 7]);
+
+    // 24 in three cells is 7+8+9
+    let cage = Cage::new(24,vec![(0,0),(1,0),(2,0)]);
+    assert_eq!(cage.allowed(shape,&[all,all,1 << 6]),vec![0,0,0]);
+}
+
+#[test]
+fn test_cage_conflicts(){
+    use crate::gameboard::Gameboard;
+
+    let mut gb = Gameboard::new();
+    gb.set_cages(vec![Cage::new(10,vec![(0,0),(0,1),(1,0)])]);
+
+    gb.set((0,0),4);
+    gb.set((0,1),5);
+    assert!(!gb.invalid());
+    gb.set((1,0),4);
+    assert!(gb.get_invalid((0,0)) && gb.get_invalid((1,0)));
+    gb.set((1,0),1);
+    assert!(!gb.invalid());
+    gb.set((0,1),6);
+    assert!(gb.get_invalid((0,0)) && gb.get_invalid((0,1)) && gb.get_invalid((1,0)));
+    gb.set((1,0),0);
+    assert!(!gb.invalid());
+    gb.set((0,1),7);
+    assert!(gb.get_invalid((0,1)) && !gb.get_invalid((1,0)));
+}
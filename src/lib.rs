@@ -0,0 +1,56 @@
+#![deny(missing_docs)]
+
+//! A Sudoku library.
+//!
+//! The model, solver and generator are always available. The piston based
+//! front-end (controller and view) is only compiled with the `gui` feature.
+extern crate rand;
+extern crate rand_chacha;
+#[cfg(feature = "gui")]
+extern crate piston;
+#[cfg(feature = "gui")]
+extern crate graphics;
+
+pub use crate::shape::{Grid,Pos,Shape};
+pub use crate::constraint::{Boxes,Columns,Constraint,Rows,Rule};
+pub use crate::gameboard::{Gameboard,NoteKind,ParseBoardError,SIZE};
+pub use crate::solver::{SolutionCount,Solver,SudokuSolver};
+pub use crate::dancing_links::DancingLinksSolver;
+pub use crate::logical_solver::{LogicalSolver,Step,Technique};
+pub use crate::rating::{Difficulty,Rating};
+pub use crate::puzzle_file::{PuzzleReader,PuzzleWriter};
+pub use crate::save::SaveGame;
+pub use crate::history::History;
+pub use crate::hint::{Hint,HintLevel};
+pub use crate::mistakes::{CheckMode,MistakeTracker};
+pub use crate::timer::Timer;
+pub use crate::stats::{Record,Stats};
+pub use crate::killer::{Cage,Cages};
+pub use crate::jigsaw::Regions;
+pub use crate::generator::{Budget,Generator,GenerateError,GeneratorOptions,Symmetry};
+#[cfg(feature = "gui")]
+pub use crate::gameboard_controller::GameboardController;
+#[cfg(feature = "gui")]
+pub use crate::gameboard_view::{GameboardView,GameboardViewSettings};
+
+pub mod shape;
+pub mod constraint;
+pub mod gameboard;
+pub mod solver;
+pub mod dancing_links;
+pub mod logical_solver;
+pub mod rating;
+pub mod puzzle_file;
+pub mod save;
+pub mod history;
+pub mod hint;
+pub mod mistakes;
+pub mod timer;
+pub mod stats;
+pub mod killer;
+pub mod jigsaw;
+pub mod generator;
+#[cfg(feature = "gui")]
+pub mod gameboard_controller;
+#[cfg(feature = "gui")]
+pub mod gameboard_view;
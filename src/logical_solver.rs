@@ -0,0 +1,689 @@
+//! Human-style logical Sudoku solver
+//!
+//! Unlike `Solver` this never guesses. It only applies techniques a human
+//! would use and records every step, so the trace can be used for hints,
+//! difficulty rating and teaching.
+
+use std::fmt;
+
+use crate::constraint::Rule;
+use crate::gameboard::Gameboard;
+use crate::shape::{Grid,Pos,Shape};
+
+/// Human solving technique
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub enum Technique{
+    /// The only cell of a unit which can hold a digit.
+    HiddenSingle,
+    /// A cell with only one candidate left.
+    NakedSingle,
+    /// Candidates of a section (or other unit) confined to one row or column.
+    PointingPair,
+    /// Candidates of a row or column confined to one section (or other unit).
+    BoxLineReduction,
+    /// Two cells of a unit sharing the same two candidates.
+    NakedPair,
+    /// Candidates of a digit in two rows (columns) confined to two columns (rows).
+    XWing,
+    /// Two digits of a unit confined to the same two cells.
+    HiddenPair,
+    /// Three cells of a unit sharing three candidates.
+    NakedTriple,
+    /// Candidates of a digit in three rows (columns) confined to three columns (rows).
+    Swordfish,
+    /// Three digits of a unit confined to the same three cells.
+    HiddenTriple,
+    /// A bi-value pivot with two bi-value pincers.
+    XYWing,
+    /// Two-colouring of the conjugate pairs of a digit.
+    SimpleColoring,
+}
+
+impl Technique{
+    /// All techniques, from the simplest to the hardest.
+    pub const ALL : [Technique;12] = [
+        Technique::HiddenSingle,
+        Technique::NakedSingle,
+        Technique::PointingPair,
+        Technique::BoxLineReduction,
+        Technique::NakedPair,
+        Technique::XWing,
+        Technique::HiddenPair,
+        Technique::NakedTriple,
+        Technique::Swordfish,
+        Technique::HiddenTriple,
+        Technique::XYWing,
+        Technique::SimpleColoring,
+    ];
+
+    /// Human readable name
+    pub fn name(self)->&'static str{
+        match self{
+            Technique::HiddenSingle => "Hidden Single",
+            Technique::NakedSingle => "Naked Single",
+            Technique::PointingPair => "Pointing Pair",
+            Technique::BoxLineReduction => "Box/Line Reduction",
+            Technique::NakedPair => "Naked Pair",
+            Technique::XWing => "X-Wing",
+            Technique::HiddenPair => "Hidden Pair",
+            Technique::NakedTriple => "Naked Triple",
+            Technique::Swordfish => "Swordfish",
+            Technique::HiddenTriple => "Hidden Triple",
+            Technique::XYWing => "XY-Wing",
+            Technique::SimpleColoring => "Simple Coloring",
+        }
+    }
+
+    /// Numeric rating in the style of Sudoku Explainer
+    pub fn rating(self)->f32{
+        match self{
+            Technique::HiddenSingle => 1.5,
+            Technique::NakedSingle => 2.3,
+            Technique::PointingPair => 2.6,
+            Technique::BoxLineReduction => 2.8,
+            Technique::NakedPair => 3.0,
+            Technique::XWing => 3.2,
+            Technique::HiddenPair => 3.4,
+            Technique::NakedTriple => 3.6,
+            Technique::Swordfish => 3.8,
+            Technique::HiddenTriple => 4.0,
+            Technique::XYWing => 4.2,
+            Technique::SimpleColoring => 4.5,
+        }
+    }
+}
+
+impl fmt::Display for Technique{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f,"{}",self.name())
+    }
+}
+
+/// One step of the logical solver
+#[derive(Clone,Debug,PartialEq)]
+pub struct Step{
+    /// Applied technique
+    pub technique : Technique,
+    /// Cells forming the pattern
+    pub cells : Vec<Pos>,
+    /// Digits placed by this step
+    pub placements : Vec<(Pos,u8)>,
+    /// Candidates removed by this step
+    pub eliminations : Vec<(Pos,u8)>,
+}
+
+impl fmt::Display for Step{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        let changes = self.placements.iter()
+            .map(|&((i,j),d)| format!("r{}c{}={}",i+1,j+1,d))
+            .chain(self.eliminations.iter()
+                .map(|&((i,j),d)| format!("r{}c{}<>{}",i+1,j+1,d)))
+            .collect::<Vec<String>>();
+
+        write!(f,"{}: {}",self.technique,changes.join(", "))
+    }
+}
+
+/// Logical Sudoku solver
+pub struct LogicalSolver{
+    /// Size of the boxes
+    shape : Shape,
+    /// Rules of the board
+    rules : Vec<Rule>,
+    /// Groups of cells which hold different digits under the rules
+    units : Vec<Vec<Pos>>,
+    /// Cells sharing a unit with every cell
+    peers : Vec<Vec<Vec<Pos>>>,
+    /// Stores the content of the cells. `0` is an empty cell.
+    cells : Grid,
+    /// Candidate bit mask of every cell. Filled cells have no candidates.
+    candidates : Vec<Vec<u32>>,
+}
+
+impl LogicalSolver{
+    /// Create new LogicalSolver from the current state of a gameboard
+    pub fn new(gb: &Gameboard)->LogicalSolver{
+        let shape = gb.shape();
+        let n = shape.size();
+        let mut solver = LogicalSolver{
+            shape,
+            rules : gb.rules().to_vec(),
+            units : gb.units(),
+            peers : (0..n).map(|i| (0..n).map(|j| gb.peers((i,j))).collect()).collect(),
+            cells : gb.copy_cells(),
+            candidates : vec![vec![0;n];n],
+        };
+
+        for p in shape.cells(){
+            if solver.get(p) == 0{
+                let seen = solver.peers[p.0][p.1].iter()
+                    .fold(0,|s,&q| s | (1 << solver.get(q)));
+                solver.candidates[p.0][p.1] = shape.all_digits() & !seen;
+            }
+        }
+        solver.prune();
+
+        solver
+    }
+
+    /// Get cell value
+    pub fn get(&self,ind: Pos)->u8{
+        self.cells[ind.0][ind.1]
+    }
+
+    /// Candidate bit mask of a cell. Bit `d` is set if digit `d` is possible.
+    pub fn candidates(&self,ind: Pos)->u32{
+        self.candidates[ind.0][ind.1]
+    }
+
+    /// get all cell data
+    pub fn copy_cells(&self)->Grid{
+        self.cells.clone()
+    }
+
+    /// if every cell is filled, returns true
+    pub fn finished(&self)->bool{
+        self.shape.cells().all(|p| self.get(p) != 0)
+    }
+
+    /// Apply techniques until the board is finished or no technique applies.
+    /// Returns the applied steps in order.
+    pub fn solve(&mut self)->Vec<Step>{
+        let mut steps = vec![];
+
+        while let Some(step) = self.next_step(){
+            self.apply(&step);
+            steps.push(step);
+        }
+
+        steps
+    }
+
+    /// Find the next step using the simplest applicable technique
+    pub fn next_step(&self)->Option<Step>{
+        Technique::ALL.iter()
+            .filter_map(|&t| self.find(t))
+            .next()
+    }
+
+    /// Find a step using a specific technique
+    pub fn find(&self,technique: Technique)->Option<Step>{
+        match technique{
+            Technique::HiddenSingle => self.find_hidden_single(),
+            Technique::NakedSingle => self.find_naked_single(),
+            Technique::PointingPair => self.find_pointing(),
+            Technique::BoxLineReduction => self.find_box_line(),
+            Technique::NakedPair => self.find_naked_subset(2,technique),
+            Technique::XWing => self.find_fish(2,technique),
+            Technique::HiddenPair => self.find_hidden_subset(2,technique),
+            Technique::NakedTriple => self.find_naked_subset(3,technique),
+            Technique::Swordfish => self.find_fish(3,technique),
+            Technique::HiddenTriple => self.find_hidden_subset(3,technique),
+            Technique::XYWing => self.find_xy_wing(),
+            Technique::SimpleColoring => self.find_simple_coloring(),
+        }
+    }
+
+    /// Apply placements and eliminations of a step
+    pub fn apply(&mut self,step: &Step){
+        for &(p,d) in step.placements.iter(){
+            self.cells[p.0][p.1] = d;
+            self.candidates[p.0][p.1] = 0;
+            for &q in self.peers[p.0][p.1].iter(){
+                self.candidates[q.0][q.1] &= !(1 << d);
+            }
+        }
+
+        for &(p,d) in step.eliminations.iter(){
+            self.candidates[p.0][p.1] &= !(1 << d);
+        }
+        self.prune();
+    }
+
+    /// Remove the candidates the rules rule out beyond their units
+    fn prune(&mut self){
+        let mut masks = self.shape.cells()
+            .map(|p| match self.get(p){
+                0 => self.candidates(p),
+                d => 1 << d,
+            })
+            .collect::<Vec<u32>>();
+        for rule in self.rules.iter(){
+            rule.prune(self.shape,&mut masks);
+        }
+
+        for (p,mask) in self.shape.cells().zip(masks){
+            if self.get(p) == 0{
+                self.candidates[p.0][p.1] &= mask;
+            }
+        }
+    }
+
+    /// Units which must hold every digit
+    fn full_units(&self)->impl Iterator<Item=&Vec<Pos>>{
+        let n = self.shape.size();
+
+        self.units.iter().filter(move |unit| unit.len() == n)
+    }
+
+    /// if two different cells share a unit, returns true
+    fn sees(&self,a: Pos,b: Pos)->bool{
+        self.peers[a.0][a.1].contains(&b)
+    }
+
+    /// if all cells of a unit share a row or a column, returns true
+    fn is_line(unit: &[Pos])->bool{
+        unit.iter().all(|p| p.0 == unit[0].0) || unit.iter().all(|p| p.1 == unit[0].1)
+    }
+
+    /// Remove candidate `d` of a cell, e.g. one the player already ruled out
+    pub fn eliminate(&mut self,p: Pos,d: u8){
+        self.candidates[p.0][p.1] &= !(1 << d);
+    }
+
+    /// if digit `d` is a candidate of the cell, returns true
+    fn has(&self,p: Pos,d: u8)->bool{
+        self.candidates(p) & (1 << d) != 0
+    }
+
+    /// Cells of `unit` which have digit `d` as a candidate
+    fn positions(&self,unit: &[Pos],d: u8)->Vec<Pos>{
+        unit.iter().cloned().filter(|&p| self.has(p,d)).collect()
+    }
+
+    /// Candidates `d` of `cells` which aren't in `except`
+    fn eliminations_in(&self,cells: &[Pos],except: &[Pos],d: u8)->Vec<(Pos,u8)>{
+        cells.iter()
+            .filter(|p| !except.contains(p) && self.has(**p,d))
+            .map(|&p| (p,d))
+            .collect()
+    }
+
+    fn find_hidden_single(&self)->Option<Step>{
+        for unit in self.full_units(){
+            for d in self.shape.digits(){
+                let ps = self.positions(unit,d);
+                if ps.len() == 1{
+                    return Some(Step{
+                        technique : Technique::HiddenSingle,
+                        cells : ps.clone(),
+                        placements : vec![(ps[0],d)],
+                        eliminations : vec![],
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_naked_single(&self)->Option<Step>{
+        self.shape.cells()
+            .find(|&p| self.candidates(p).count_ones() == 1)
+            .map(|p|{
+                let d = self.candidates(p).trailing_zeros() as u8;
+                Step{
+                    technique : Technique::NakedSingle,
+                    cells : vec![p],
+                    placements : vec![(p,d)],
+                    eliminations : vec![],
+                }
+            })
+    }
+
+    fn find_pointing(&self)->Option<Step>{
+        let sections = self.full_units().filter(|unit| !LogicalSolver::is_line(unit));
+
+        for section in sections{
+            for d in self.shape.digits(){
+                let ps = self.positions(section,d);
+                if let Some(step) = self.find_confined(section,ps,d,Technique::PointingPair,|_| true){
+                    return Some(step);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_box_line(&self)->Option<Step>{
+        let lines = self.full_units().filter(|unit| LogicalSolver::is_line(unit));
+
+        for line in lines{
+            for d in self.shape.digits(){
+                let ps = self.positions(line,d);
+                let to = |unit: &[Pos]| !LogicalSolver::is_line(unit);
+                if let Some(step) = self.find_confined(line,ps,d,Technique::BoxLineReduction,to){
+                    return Some(step);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Candidates `d` of the full unit `from` are the cells `ps`. If another
+    /// unit accepted by `to` contains all of them, `d` can be removed from
+    /// the rest of that unit.
+    fn find_confined<F>(&self,from: &[Pos],ps: Vec<Pos>,d: u8,technique: Technique,to: F)->Option<Step>
+        where F: Fn(&[Pos]) -> bool
+    {
+        if ps.is_empty(){
+            return None;
+        }
+
+        let units = self.units.iter()
+            .filter(|unit| unit.as_slice() != from && to(unit))
+            .filter(|unit| ps.iter().all(|p| unit.contains(p)));
+        for unit in units{
+            let eliminations = self.eliminations_in(unit,from,d);
+            if !eliminations.is_empty(){
+                return Some(Step{
+                    technique,
+                    cells : ps,
+                    placements : vec![],
+                    eliminations,
+                });
+            }
+        }
+
+        None
+    }
+
+    fn find_naked_subset(&self,n: usize,technique: Technique)->Option<Step>{
+        for unit in self.units.iter(){
+            let empty = unit.iter().cloned()
+                .filter(|&p|{
+                    let c = self.candidates(p).count_ones() as usize;
+                    2 <= c && c <= n
+                })
+                .collect::<Vec<Pos>>();
+
+            for combo in combinations(empty.len(),n){
+                let cells = combo.iter().map(|&k| empty[k]).collect::<Vec<Pos>>();
+                let mask = cells.iter().fold(0,|s,&p| s | self.candidates(p));
+                if mask.count_ones() as usize != n{
+                    continue;
+                }
+
+                let eliminations = self.shape.digits()
+                    .filter(|d| mask & (1 << d) != 0)
+                    .flat_map(|d| self.eliminations_in(unit,&cells,d))
+                    .collect::<Vec<(Pos,u8)>>();
+                if !eliminations.is_empty(){
+                    return Some(Step{
+                        technique,
+                        cells,
+                        placements : vec![],
+                        eliminations,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_hidden_subset(&self,n: usize,technique: Technique)->Option<Step>{
+        for unit in self.full_units(){
+            let ds = self.shape.digits()
+                .filter(|&d|{
+                    let c = self.positions(unit,d).len();
+                    2 <= c && c <= n
+                })
+                .collect::<Vec<u8>>();
+
+            for combo in combinations(ds.len(),n){
+                let mask = combo.iter().fold(0u32,|s,&k| s | (1 << ds[k]));
+                let cells = unit.iter().cloned()
+                    .filter(|&p| self.candidates(p) & mask != 0)
+                    .collect::<Vec<Pos>>();
+                if cells.len() != n{
+                    continue;
+                }
+
+                let eliminations = cells.iter()
+                    .flat_map(|&p| self.shape.digits()
+                        .filter(move |d| mask & (1 << d) == 0)
+                        .map(move |d| (p,d)))
+                    .filter(|&(p,d)| self.has(p,d))
+                    .collect::<Vec<(Pos,u8)>>();
+                if !eliminations.is_empty(){
+                    return Some(Step{
+                        technique,
+                        cells,
+                        placements : vec![],
+                        eliminations,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_fish(&self,n: usize,technique: Technique)->Option<Step>{
+        for d in self.shape.digits(){
+            // rows as base lines, then columns as base lines
+            for &transpose in [false,true].iter(){
+                let line = |k: usize| if transpose {self.shape.col_cells(k)} else {self.shape.row_cells(k)};
+                let cross = |p: Pos| if transpose {p.0} else {p.1};
+
+                let base = (0..self.shape.size())
+                    .filter(|&k|{
+                        let c = self.positions(&line(k),d).len();
+                        2 <= c && c <= n
+                    })
+                    .collect::<Vec<usize>>();
+
+                for combo in combinations(base.len(),n){
+                    let lines = combo.iter().map(|&k| base[k]).collect::<Vec<usize>>();
+                    let cells = lines.iter()
+                        .flat_map(|&k| self.positions(&line(k),d))
+                        .collect::<Vec<Pos>>();
+                    let mut cover = cells.iter().map(|&p| cross(p)).collect::<Vec<usize>>();
+                    cover.sort();
+                    cover.dedup();
+                    if cover.len() != n{
+                        continue;
+                    }
+
+                    let eliminations = cover.iter()
+                        .flat_map(|&k|{
+                            let cover_line = if transpose {self.shape.row_cells(k)} else {self.shape.col_cells(k)};
+                            self.eliminations_in(&cover_line,&cells,d)
+                        })
+                        .collect::<Vec<(Pos,u8)>>();
+                    if !eliminations.is_empty(){
+                        return Some(Step{
+                            technique,
+                            cells,
+                            placements : vec![],
+                            eliminations,
+                        });
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_xy_wing(&self)->Option<Step>{
+        let bivalue = self.shape.cells()
+            .filter(|&p| self.candidates(p).count_ones() == 2)
+            .collect::<Vec<Pos>>();
+
+        for &pivot in bivalue.iter(){
+            let xy = self.candidates(pivot);
+
+            for &a in bivalue.iter().filter(|&&a| self.sees(pivot,a)){
+                let xz = self.candidates(a);
+                if (xy & xz).count_ones() != 1{
+                    continue;
+                }
+                let z = xz & !xy;
+                let yz = (xy & !xz) | z;
+
+                for &b in bivalue.iter().filter(|&&b| b != a && self.sees(pivot,b)){
+                    if self.candidates(b) != yz{
+                        continue;
+                    }
+
+                    let z = z.trailing_zeros() as u8;
+                    let eliminations = self.shape.cells()
+                        .filter(|&p| p != pivot && self.sees(p,a) && self.sees(p,b) && self.has(p,z))
+                        .map(|p| (p,z))
+                        .collect::<Vec<(Pos,u8)>>();
+                    if !eliminations.is_empty(){
+                        return Some(Step{
+                            technique : Technique::XYWing,
+                            cells : vec![pivot,a,b],
+                            placements : vec![],
+                            eliminations,
+                        });
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn find_simple_coloring(&self)->Option<Step>{
+        for d in self.shape.digits(){
+            // conjugate pairs: units where `d` has exactly two places
+            let pairs = self.full_units()
+                .map(|unit| self.positions(unit,d))
+                .filter(|ps| ps.len() == 2)
+                .collect::<Vec<Vec<Pos>>>();
+
+            let n = self.shape.size();
+            let mut color : Vec<Vec<Option<bool>>> = vec![vec![None;n];n];
+            for start in pairs.iter().map(|ps| ps[0]){
+                if color[start.0][start.1].is_some(){
+                    continue;
+                }
+
+                // colour the chain containing `start`
+                let mut chain = vec![start];
+                let mut stack = vec![start];
+                color[start.0][start.1] = Some(true);
+                while let Some(p) = stack.pop(){
+                    let c = color[p.0][p.1].unwrap();
+                    for ps in pairs.iter().filter(|ps| ps.contains(&p)){
+                        let q = if ps[0] == p {ps[1]} else {ps[0]};
+                        if color[q.0][q.1].is_none(){
+                            color[q.0][q.1] = Some(!c);
+                            chain.push(q);
+                            stack.push(q);
+                        }
+                    }
+                }
+
+                let colored = |c: bool| chain.iter().cloned()
+                    .filter(|p| color[p.0][p.1] == Some(c))
+                    .collect::<Vec<Pos>>();
+                let (on,off) = (colored(true),colored(false));
+
+                // colour wrap: two cells of the same colour see each other
+                for group in [&on,&off].iter(){
+                    let wrap = group.iter()
+                        .any(|&p| group.iter().any(|&q| self.sees(p,q)));
+                    if wrap{
+                        return Some(Step{
+                            technique : Technique::SimpleColoring,
+                            cells : chain,
+                            placements : vec![],
+                            eliminations : group.iter().map(|&p| (p,d)).collect(),
+                        });
+                    }
+                }
+
+                // colour trap: a cell sees both colours
+                let eliminations = self.shape.cells()
+                    .filter(|&p| self.has(p,d) && !chain.contains(&p))
+                    .filter(|&p| on.iter().any(|&q| self.sees(p,q)) && off.iter().any(|&q| self.sees(p,q)))
+                    .map(|p| (p,d))
+                    .collect::<Vec<(Pos,u8)>>();
+                if !eliminations.is_empty(){
+                    return Some(Step{
+                        technique : Technique::SimpleColoring,
+                        cells : chain,
+                        placements : vec![],
+                        eliminations,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// All `k`-element combinations of `0..n` in lexicographic order
+fn combinations(n: usize,k: usize) -> Vec<Vec<usize>>{
+    let mut v = vec![];
+    let mut combo = (0..k).collect::<Vec<usize>>();
+
+    if k > n{
+        return v;
+    }
+
+    loop{
+        v.push(combo.clone());
+
+        // find the rightmost element which can be advanced
+        let i = match (0..k).rev().find(|&i| combo[i] < n - k + i){
+            Some(i) => i,
+            None => return v,
+        };
+        combo[i] += 1;
+        for l in i+1..k{
+            combo[l] = combo[l-1] + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+fn check_steps(quiz: &str){
+    use crate::solver::{Solver,SudokuSolver};
+
+    let gb = crate::solver::make_board(quiz);
+    let answer = Solver::new().make_answer_list(&gb,1).remove(0);
+    let mut solver = LogicalSolver::new(&gb);
+
+    for step in solver.solve(){
+        for &((i,j),d) in step.placements.iter(){
+            assert_eq!(answer[i][j],d,"{}",step);
+        }
+        for &((i,j),d) in step.eliminations.iter(){
+            assert_ne!(answer[i][j],d,"{}",step);
+        }
+    }
+}
+
+#[test]
+fn test_logical_singles(){
+    let gb = crate::solver::make_board(
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..");
+    let mut solver = LogicalSolver::new(&gb);
+    let steps = solver.solve();
+
+    assert!(solver.finished());
+    assert!(steps.iter().all(|s| s.technique <= Technique::NakedSingle));
+}
+
+#[test]
+fn test_logical_steps_sound(){
+    check_steps("4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......");
+    check_steps("52...6.........7.13...........4..8..6......5...........418.........3..2...87.....");
+    check_steps(".....6....59.....82....8....45........3........6..3.54...325..6..................");
+}
+
+#[test]
+fn test_combinations(){
+    assert_eq!(combinations(4,2).len(),6);
+    assert_eq!(combinations(3,3),vec![vec![0,1,2]]);
+    assert!(combinations(2,3).is_empty());
+}
@@ -5,7 +5,7 @@ extern crate piston;
 extern crate glutin_window;
 extern crate graphics;
 extern crate opengl_graphics;
-extern crate rand;
+extern crate sudoku;
 
 use piston::window::WindowSettings;
 use piston::event_loop::{Events,EventSettings,EventLoop};
@@ -13,17 +13,8 @@ use piston::input::RenderEvent;
 use glutin_window::GlutinWindow;
 use opengl_graphics::{OpenGL,GlGraphics,Filter,GlyphCache,TextureSettings};
 
-pub use crate::gameboard::Gameboard;
-pub use crate::gameboard_controller::GameboardController;
-pub use crate::gameboard_view::{GameboardView,GameboardViewSettings};
-pub use crate::solver::Solver;
-pub use crate::generator::Generator;
-
-mod gameboard;
-mod gameboard_controller;
-mod gameboard_view;
-mod solver;
-mod generator;
+use sudoku::{Gameboard,GameboardController,GameboardView,GameboardViewSettings,SaveGame};
+use sudoku::gameboard_controller::SAVE_PATH;
 
 fn main() {
     let opengl = OpenGL::V3_2;
@@ -33,12 +24,22 @@ fn main() {
     let mut window : GlutinWindow = settings.build()
         .expect("Cound not create window");
 
-    let mut events = Events::new(EventSettings::new().lazy(true));
+    // not lazy, so that the timer keeps ticking
+    let mut events = Events::new(EventSettings::new().max_fps(10));
     let mut gl = GlGraphics::new(opengl);
 
-    let mut gameboard = Gameboard::new();
-    gameboard.generate();
-    let mut gameboard_controller = GameboardController::new(gameboard);
+    // continue the last game if there is one
+    let mut gameboard_controller = match SaveGame::load(SAVE_PATH){
+        Ok(save) => GameboardController::resume(save),
+        Err(_) => {
+            let mut gameboard = Gameboard::new();
+            // on error the last attempt is still a valid quiz
+            if let Err(err) = gameboard.generate(){
+                eprintln!("{}",err);
+            }
+            GameboardController::new(gameboard)
+        },
+    };
     let gameboard_view_settings = GameboardViewSettings::new();
     let gameboard_view = GameboardView::new(gameboard_view_settings);
 
@@ -58,4 +59,8 @@ fn main() {
             });
         }
     }
+
+    if let Err(err) = gameboard_controller.save(){
+        eprintln!("Could not save game: {}",err);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,189 @@
+//! Checking user entries against the answer
+//!
+//! Needs the answer cached in the gameboard (see `Gameboard::solution`).
+
+use crate::gameboard::Gameboard;
+use crate::shape::Pos;
+
+/// When wrong entries are reported
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum CheckMode{
+    /// Never
+    Off,
+    /// As soon as a digit is entered
+    Immediate,
+    /// When the player asks for a check
+    OnDemand,
+}
+
+impl CheckMode{
+    /// Next mode in the order Off, Immediate, OnDemand
+    pub fn next(self) -> CheckMode{
+        match self{
+            CheckMode::Off => CheckMode::Immediate,
+            CheckMode::Immediate => CheckMode::OnDemand,
+            CheckMode::OnDemand => CheckMode::Off,
+        }
+    }
+
+    /// Name used in save files
+    pub fn name(self) -> &'static str{
+        match self{
+            CheckMode::Off => "off",
+            CheckMode::Immediate => "immediate",
+            CheckMode::OnDemand => "on-demand",
+        }
+    }
+
+    /// Mode named `name`
+    pub fn from_name(name: &str) -> Option<CheckMode>{
+        match name{
+            "off" => Some(CheckMode::Off),
+            "immediate" => Some(CheckMode::Immediate),
+            "on-demand" => Some(CheckMode::OnDemand),
+            _ => None,
+        }
+    }
+}
+
+/// Counts mistakes and remembers the reported ones
+#[derive(Clone)]
+pub struct MistakeTracker{
+    /// When wrong entries are reported
+    pub mode : CheckMode,
+    /// Number of mistakes made so far
+    pub mistakes : u32,
+    /// The game is over after this many mistakes, `None` for no limit
+    pub max_mistakes : Option<u32>,
+    /// Reported wrong entries
+    marked : Vec<(Pos,u8)>,
+}
+
+impl Default for MistakeTracker{
+    fn default() -> MistakeTracker{
+        MistakeTracker::new(CheckMode::Off,None)
+    }
+}
+
+impl MistakeTracker{
+    /// Create new MistakeTracker
+    pub fn new(mode: CheckMode,max_mistakes: Option<u32>) -> MistakeTracker{
+        MistakeTracker{
+            mode,
+            mistakes : 0,
+            max_mistakes,
+            marked : vec![],
+        }
+    }
+
+    /// Report a digit the player entered at `ind`.
+    /// Returns `true` if it was counted as a mistake.
+    pub fn entered(&mut self,gb: &Gameboard,ind: Pos) -> bool{
+        if self.mode != CheckMode::Immediate || !gb.wrong(ind){
+            return false;
+        }
+
+        self.mark(ind,gb.get(ind));
+        true
+    }
+
+    /// Check every entry now. Wrong entries which weren't reported yet count
+    /// as mistakes. Returns the number of new mistakes.
+    pub fn check(&mut self,gb: &Gameboard) -> usize{
+        let wrong = gb.shape().cells()
+            .filter(|&ind| gb.wrong(ind) && !self.is_marked(gb,ind))
+            .collect::<Vec<Pos>>();
+
+        for &ind in wrong.iter(){
+            self.mark(ind,gb.get(ind));
+        }
+
+        wrong.len()
+    }
+
+    /// if the entry of a cell was reported as wrong and is still there,
+    /// returns true
+    pub fn is_marked(&self,gb: &Gameboard,ind: Pos) -> bool{
+        let d = gb.get(ind);
+
+        d != 0 && self.marked.contains(&(ind,d))
+    }
+
+    /// Reported wrong entries: the cell and the digit
+    pub fn marked(&self) -> &[(Pos,u8)]{
+        &self.marked
+    }
+
+    /// Replace the reported wrong entries, e.g. of a saved game.
+    /// The number of mistakes is left as it is.
+    pub fn set_marked(&mut self,marked: Vec<(Pos,u8)>){
+        self.marked = marked;
+    }
+
+    /// if the mistake limit was reached, returns true
+    pub fn game_over(&self) -> bool{
+        self.max_mistakes.is_some_and(|max| self.mistakes >= max)
+    }
+
+    /// Start over for a new quiz, keeping the mode and the limit
+    pub fn reset(&mut self){
+        self.mistakes = 0;
+        self.marked.clear();
+    }
+
+    /// Count a mistake and remember it
+    fn mark(&mut self,ind: Pos,d: u8){
+        self.mistakes += 1;
+        if !self.marked.contains(&(ind,d)){
+            self.marked.push((ind,d));
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_board() -> Gameboard{
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    gb.cache_solution();
+
+    gb
+}
+
+#[test]
+fn test_immediate(){
+    let mut gb = test_board();
+    let mut tracker = MistakeTracker::new(CheckMode::Immediate,Some(3));
+
+    // the answer of (0,0) is 4
+    for &d in [5,4,5,5].iter(){
+        gb.set((0,0),d);
+        tracker.entered(&gb,(0,0));
+    }
+
+    assert_eq!(tracker.mistakes,3);
+    assert!(tracker.is_marked(&gb,(0,0)));
+    assert!(tracker.game_over());
+
+    gb.set((0,0),0);
+    assert!(!tracker.is_marked(&gb,(0,0)));
+}
+
+#[test]
+fn test_on_demand(){
+    let mut gb = test_board();
+    let mut tracker = MistakeTracker::new(CheckMode::OnDemand,None);
+
+    gb.set((0,0),5);
+    assert!(!tracker.entered(&gb,(0,0)));
+    gb.set((0,1),8);
+
+    assert_eq!(tracker.check(&gb),1);
+    assert!(tracker.is_marked(&gb,(0,0)));
+    assert!(!tracker.is_marked(&gb,(0,1)));
+
+    // reported mistakes aren't counted twice
+    assert_eq!(tracker.check(&gb),0);
+    assert_eq!(tracker.mistakes,1);
+    assert!(!tracker.game_over());
+}
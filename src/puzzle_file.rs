@@ -0,0 +1,201 @@
+//! Reading and writing puzzle collections
+//!
+//! A puzzle file has one quiz per line in the line format (81 characters
+//! for the classic board).
+//! Anything after the grid (ratings, names, ...) is kept as a comment.
+//! Blank lines and lines starting with `#` are skipped.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self,BufRead,BufReader,Write};
+use std::path::Path;
+
+use crate::gameboard::{Gameboard,ParseBoardError};
+
+/// One quiz of a puzzle file
+#[derive(Clone)]
+pub struct Entry{
+    /// Line number (1-based)
+    pub line : usize,
+    /// The quiz
+    pub gameboard : Gameboard,
+    /// Text after the grid, trimmed
+    pub comment : String,
+}
+
+/// Error of reading a puzzle file
+#[derive(Debug)]
+pub enum ReadError{
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// A line couldn't be parsed.
+    Parse{
+        /// Line number (1-based)
+        line : usize,
+        /// What was wrong with the grid
+        error : ParseBoardError,
+    },
+}
+
+impl fmt::Display for ReadError{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ReadError::Io(e) => write!(f,"{}",e),
+            ReadError::Parse{line,error} => write!(f,"line {}: {}",line,error),
+        }
+    }
+}
+
+impl Error for ReadError{
+    fn source(&self) -> Option<&(dyn Error + 'static)>{
+        match self{
+            ReadError::Io(e) => Some(e),
+            ReadError::Parse{error,..} => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError{
+    fn from(e: io::Error) -> ReadError{
+        ReadError::Io(e)
+    }
+}
+
+/// Streaming reader of puzzle files
+///
+/// Quizzes are parsed lazily, one line at a time.
+pub struct PuzzleReader<R>{
+    /// Lines of the underlying reader
+    lines : io::Lines<R>,
+    /// Number of lines read so far
+    line : usize,
+}
+
+impl PuzzleReader<BufReader<File>>{
+    /// Open a puzzle file
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<PuzzleReader<BufReader<File>>>{
+        Ok(PuzzleReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> PuzzleReader<R>{
+    /// Create new PuzzleReader
+    pub fn new(reader: R) -> PuzzleReader<R>{
+        PuzzleReader{
+            lines : reader.lines(),
+            line : 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PuzzleReader<R>{
+    type Item = Result<Entry,ReadError>;
+
+    fn next(&mut self) -> Option<Result<Entry,ReadError>>{
+        for text in self.lines.by_ref(){
+            self.line += 1;
+
+            let text = match text{
+                Ok(text) => text,
+                Err(e) => return Some(Err(ReadError::Io(e))),
+            };
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#'){
+                continue;
+            }
+
+            return Some(parse_line(self.line,text));
+        }
+
+        None
+    }
+}
+
+/// Split a line into the grid and the comment after it
+fn parse_line(line: usize,text: &str) -> Result<Entry,ReadError>{
+    let end = text.find(|c: char| c.is_whitespace() || ";,|#".contains(c))
+        .unwrap_or(text.len());
+    let (grid,rest) = text.split_at(end);
+
+    let gameboard = grid.parse::<Gameboard>()
+        .map_err(|error| ReadError::Parse{line,error})?;
+    let comment = rest.trim_start_matches(|c: char| c.is_whitespace() || ";,|#".contains(c))
+        .trim_end()
+        .to_string();
+
+    Ok(Entry{line,gameboard,comment})
+}
+
+/// Writer of puzzle files
+pub struct PuzzleWriter<W>{
+    /// Underlying writer
+    writer : W,
+}
+
+impl<W: Write> PuzzleWriter<W>{
+    /// Create new PuzzleWriter
+    pub fn new(writer: W) -> PuzzleWriter<W>{
+        PuzzleWriter{
+            writer,
+        }
+    }
+
+    /// Write one quiz with an optional comment (e.g. its rating)
+    pub fn write(&mut self,gb: &Gameboard,comment: &str) -> io::Result<()>{
+        if comment.is_empty(){
+            writeln!(self.writer,"{}",gb)
+        }else{
+            writeln!(self.writer,"{} {}",gb,comment)
+        }
+    }
+
+    /// Write a comment line
+    pub fn write_comment(&mut self,comment: &str) -> io::Result<()>{
+        writeln!(self.writer,"# {}",comment)
+    }
+
+    /// Flush and return the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W>{
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[test]
+fn test_read_puzzles(){
+    let text = "\
+# test collection
+
+..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..  ED=1.5
+4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......;hard
+12x
+";
+    let entries = PuzzleReader::new(text.as_bytes()).collect::<Vec<_>>();
+    assert_eq!(entries.len(),3);
+
+    let first = entries[0].as_ref().unwrap();
+    assert_eq!(first.line,3);
+    assert_eq!(first.comment,"ED=1.5");
+    assert_eq!(first.gameboard.get((0,2)),3);
+
+    let second = entries[1].as_ref().unwrap();
+    assert_eq!(second.line,4);
+    assert_eq!(second.comment,"hard");
+
+    match entries[2]{
+        Err(ReadError::Parse{line : 5,error : ParseBoardError::Length(3)}) => {},
+        _ => panic!("expected a parse error on line 5"),
+    }
+}
+
+#[test]
+fn test_write_puzzles(){
+    let line = "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+    let mut writer = PuzzleWriter::new(vec![]);
+
+    writer.write(&line.parse().unwrap(),"Easy").unwrap();
+    let text = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+    assert_eq!(text,format!("{} Easy\n",line));
+}
@@ -0,0 +1,184 @@
+//! Difficulty rating of Sudoku quizzes
+//!
+//! A quiz is rated by solving it with `LogicalSolver` and looking at the
+//! hardest technique it required.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::gameboard::Gameboard;
+use crate::logical_solver::{LogicalSolver,Technique};
+
+/// Numeric rating of a quiz which can't be solved without guessing.
+pub const GUESS_RATING : f32 = 10.0;
+
+/// Number of uses of the hardest technique which makes a quiz one level harder.
+const REPEAT_THRESHOLD : usize = 3;
+
+/// Difficulty level
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub enum Difficulty{
+    /// Hidden singles only.
+    Easy,
+    /// Naked singles and intersections.
+    Medium,
+    /// Pairs, X-Wing and naked triples.
+    Hard,
+    /// Swordfish, hidden triples, XY-Wing and coloring.
+    Expert,
+    /// Needs guessing (or the hardest techniques many times).
+    Evil,
+}
+
+impl Difficulty{
+    /// All levels, from the easiest to the hardest.
+    pub const ALL : [Difficulty;5] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+        Difficulty::Evil,
+    ];
+
+    /// Human readable name
+    pub fn name(self)->&'static str{
+        match self{
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+            Difficulty::Evil => "Evil",
+        }
+    }
+
+    /// Level of a numeric rating
+    pub fn from_score(score: f32)->Difficulty{
+        match score{
+            s if s <= 1.5 => Difficulty::Easy,
+            s if s <= 2.8 => Difficulty::Medium,
+            s if s <= 3.6 => Difficulty::Hard,
+            s if s < GUESS_RATING => Difficulty::Expert,
+            _ => Difficulty::Evil,
+        }
+    }
+
+    /// One level harder (`Evil` stays `Evil`)
+    fn harder(self)->Difficulty{
+        match self{
+            Difficulty::Easy => Difficulty::Medium,
+            Difficulty::Medium => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Expert,
+            Difficulty::Expert | Difficulty::Evil => Difficulty::Evil,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f,"{}",self.name())
+    }
+}
+
+impl FromStr for Difficulty{
+    type Err = String;
+
+    /// Parse a level name, ignoring case
+    fn from_str(name: &str) -> Result<Difficulty,String>{
+        Difficulty::ALL.iter()
+            .cloned()
+            .find(|d| d.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown difficulty {:?}",name))
+    }
+}
+
+/// Result of rating a quiz
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rating{
+    /// Rating of the hardest step (`GUESS_RATING` if guessing is needed)
+    pub score : f32,
+    /// Difficulty level
+    pub difficulty : Difficulty,
+    /// Hardest technique used
+    pub hardest : Option<Technique>,
+    /// Number of logical steps
+    pub steps : usize,
+    /// false if the logical solver got stuck
+    pub solved : bool,
+}
+
+impl fmt::Display for Rating{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f,"{} ({:.1})",self.difficulty,self.score)
+    }
+}
+
+/// Rate the current cells of a gameboard.
+///
+/// The level comes from the hardest technique required. If that technique
+/// (beyond singles) is needed `REPEAT_THRESHOLD` times or more, the quiz is
+/// rated one level harder.
+pub fn rate(gb: &Gameboard)->Rating{
+    let mut solver = LogicalSolver::new(gb);
+    let steps = solver.solve();
+    let solved = solver.finished();
+
+    let hardest = steps.iter().map(|s| s.technique).max();
+    let score = match (solved,hardest){
+        (false,_) => GUESS_RATING,
+        (true,Some(t)) => t.rating(),
+        (true,None) => 0.0,
+    };
+
+    let mut difficulty = Difficulty::from_score(score);
+    if let (true,Some(t)) = (solved,hardest){
+        let repeat = steps.iter().filter(|s| s.technique == t).count();
+        if t > Technique::NakedSingle && repeat >= REPEAT_THRESHOLD{
+            difficulty = difficulty.harder();
+        }
+    }
+
+    Rating{
+        score,
+        difficulty,
+        hardest,
+        steps : steps.len(),
+        solved,
+    }
+}
+
+#[test]
+fn test_rate_easy(){
+    // project euler #96 grid 01, hidden singles only
+    let gb = crate::solver::make_board(
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..");
+    let rating = rate(&gb);
+
+    assert!(rating.solved);
+    assert_eq!(rating.hardest,Some(Technique::HiddenSingle));
+    assert_eq!(rating.difficulty,Difficulty::Easy);
+}
+
+#[test]
+fn test_rate_guess(){
+    let gb = crate::solver::make_board(
+        "1.......2.9.4...5...6...7...5.9.3.......7.......85..4.7.....6...3...9.8...2.....1");
+    let rating = rate(&gb);
+
+    assert!(!rating.solved);
+    assert_eq!(rating.score,GUESS_RATING);
+    assert_eq!(rating.difficulty,Difficulty::Evil);
+}
+
+#[test]
+fn test_difficulty_from_str(){
+    assert_eq!("expert".parse::<Difficulty>(),Ok(Difficulty::Expert));
+    assert!("impossible".parse::<Difficulty>().is_err());
+}
+
+#[test]
+fn test_difficulty_from_score(){
+    assert_eq!(Difficulty::from_score(Technique::HiddenSingle.rating()),Difficulty::Easy);
+    assert_eq!(Difficulty::from_score(Technique::PointingPair.rating()),Difficulty::Medium);
+    assert_eq!(Difficulty::from_score(Technique::XWing.rating()),Difficulty::Hard);
+    assert_eq!(Difficulty::from_score(Technique::XYWing.rating()),Difficulty::Expert);
+}
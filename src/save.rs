@@ -0,0 +1,484 @@
+//! Save files of games in progress
+//!
+//! A save file is a header line followed by `key value` lines:
+//!
+//! ```text
+//! sudoku-save 1
+//! givens ..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..
+//! cells 4.3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..
+//! selected 0 1
+//! notes 0 3 16 0
+//! mistakes 1
+//! check on-demand
+//! mistake 0 0 4
+//! time 95000
+//! ```
+//!
+//! `notes` lines hold the row, column, corner and centre pencil marks of a
+//! cell as digit bit masks. `check` and `max-mistakes` hold the settings of
+//! the mistake tracker and `mistake` lines the row, column and digit of every
+//! reported wrong entry. The size of the board follows from the length of
+//! the `givens` line. Boards with other than the classic rules have a
+//! `rules` line such as `rules rows columns boxes diagonals`. Killer boards
+//! have a `cage` line per cage with the sum and the row and column of every
+//! cell, e.g. `cage 10 0 0 0 1 1 0`. Jigsaw boards have a `regions` line
+//! with the layout on a single line (see `jigsaw`).
+//!
+//! Unknown keys are ignored, so newer versions can add per-game state
+//! without breaking older readers.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::constraint::{self,Rule};
+use crate::gameboard::{Gameboard,NoteKind};
+use crate::killer::Cage;
+use crate::jigsaw::Regions;
+use crate::mistakes::{CheckMode,MistakeTracker};
+#[cfg(test)]
+use crate::gameboard::SIZE;
+
+/// Header of save files
+const HEADER : &str = "sudoku-save";
+
+/// Newest save file version
+pub const VERSION : u32 = 1;
+
+/// State of a game in progress
+#[derive(Clone)]
+pub struct SaveGame{
+    /// Givens and user entries
+    pub gameboard : Gameboard,
+    /// Selected cell
+    pub selected_cell : Option<(usize,usize)>,
+    /// Mistakes made so far and the reported wrong entries
+    pub mistakes : MistakeTracker,
+    /// Playing time so far
+    pub time : Duration,
+}
+
+/// Error of loading a save file
+#[derive(Debug)]
+pub enum LoadError{
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The file is not a valid save file.
+    Format{
+        /// Line number (1-based), `None` if the file as a whole is wrong
+        line : Option<usize>,
+        /// What was wrong
+        message : String,
+    },
+}
+
+impl fmt::Display for LoadError{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            LoadError::Io(e) => write!(f,"{}",e),
+            LoadError::Format{line : Some(line),message} => write!(f,"line {}: {}",line,message),
+            LoadError::Format{line : None,message} => write!(f,"{}",message),
+        }
+    }
+}
+
+impl Error for LoadError{
+    fn source(&self) -> Option<&(dyn Error + 'static)>{
+        match self{
+            LoadError::Io(e) => Some(e),
+            LoadError::Format{..} => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError{
+    fn from(e: io::Error) -> LoadError{
+        LoadError::Io(e)
+    }
+}
+
+impl SaveGame{
+    /// Create new SaveGame
+    pub fn new(gameboard: Gameboard,selected_cell: Option<(usize,usize)>) -> SaveGame{
+        SaveGame{
+            gameboard,
+            selected_cell,
+            mistakes : MistakeTracker::default(),
+            time : Duration::from_secs(0),
+        }
+    }
+
+    /// Write the save file
+    pub fn save<P: AsRef<Path>>(&self,path: P) -> io::Result<()>{
+        fs::write(path,self.to_string())
+    }
+
+    /// Read a save file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SaveGame,LoadError>{
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for SaveGame{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        writeln!(f,"{} {}",HEADER,VERSION)?;
+        writeln!(f,"givens {:#}",self.gameboard)?;
+        writeln!(f,"cells {}",self.gameboard)?;
+        let rules = self.gameboard.rules().iter().map(|rule| rule.name()).collect::<Vec<&str>>();
+        if rules != ["rows","columns","boxes"]{
+            writeln!(f,"rules {}",rules.join(" "))?;
+        }
+        for cage in self.gameboard.cages().iter(){
+            let cells = cage.cells.iter()
+                .map(|&(i,j)| format!(" {} {}",i,j))
+                .collect::<String>();
+            writeln!(f,"cage {}{}",cage.sum,cells)?;
+        }
+        if let Some(regions) = self.gameboard.regions(){
+            writeln!(f,"regions {:#}",regions)?;
+        }
+        if let Some((i,j)) = self.selected_cell{
+            writeln!(f,"selected {} {}",i,j)?;
+        }
+        if let Some(seed) = self.gameboard.seed(){
+            writeln!(f,"seed {}",seed)?;
+        }
+        if self.mistakes.mistakes > 0{
+            writeln!(f,"mistakes {}",self.mistakes.mistakes)?;
+        }
+        if self.mistakes.mode != CheckMode::Off{
+            writeln!(f,"check {}",self.mistakes.mode.name())?;
+        }
+        if let Some(max) = self.mistakes.max_mistakes{
+            writeln!(f,"max-mistakes {}",max)?;
+        }
+        for &((i,j),d) in self.mistakes.marked().iter(){
+            writeln!(f,"mistake {} {} {}",i,j,d)?;
+        }
+        writeln!(f,"time {}",self.time.as_millis())?;
+        for (i,j) in self.gameboard.shape().cells(){
+            let corner = self.gameboard.notes((i,j),NoteKind::Corner);
+            let centre = self.gameboard.notes((i,j),NoteKind::Centre);
+            if corner != 0 || centre != 0{
+                writeln!(f,"notes {} {} {} {}",i,j,corner,centre)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SaveGame{
+    type Err = LoadError;
+
+    fn from_str(text: &str) -> Result<SaveGame,LoadError>{
+        let error = |line: usize,message: String| LoadError::Format{line : Some(line),message};
+        let missing = |what: &str| LoadError::Format{line : None,message : format!("missing {}",what)};
+        let mut lines = text.lines().enumerate().map(|(n,l)| (n+1,l.trim()));
+
+        // header
+        let version = lines.next()
+            .and_then(|(_,l)| l.strip_prefix(HEADER))
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| error(1,"not a save file".to_string()))?;
+        if version > VERSION{
+            return Err(error(1,format!("unsupported version {}",version)));
+        }
+
+        let mut givens = None;
+        let mut cells = None;
+        let mut rules = None;
+        let mut cages = vec![];
+        let mut regions = None;
+        let mut selected_cell = None;
+        let mut seed = None;
+        let mut notes = vec![];
+        let mut mistakes = MistakeTracker::default();
+        let mut marked = vec![];
+        let mut time = Duration::from_secs(0);
+
+        for (n,l) in lines.filter(|(_,l)| !l.is_empty()){
+            let (key,value) = match l.find(char::is_whitespace){
+                Some(k) => (&l[..k],l[k..].trim()),
+                None => (l,""),
+            };
+
+            match key{
+                "givens" => givens = Some(value.parse::<Gameboard>()
+                    .map_err(|e| error(n,e.to_string()))?),
+                "cells" => cells = Some((n,value.parse::<Gameboard>()
+                    .map_err(|e| error(n,e.to_string()))?)),
+                "rules" => rules = Some(value.split_whitespace()
+                    .map(|name| match name{
+                        // the cages and regions come from their own lines
+                        "cages" | "regions" => Ok((name,None)),
+                        _ => constraint::from_name(name).map(|rule| (name,Some(rule)))
+                            .ok_or_else(|| error(n,format!("unknown rule {:?}",name))),
+                    })
+                    .collect::<Result<Vec<(&str,Option<Rule>)>,_>>()?),
+                "cage" => {
+                    let v = value.split_whitespace()
+                        .map(|x| x.parse::<usize>())
+                        .collect::<Result<Vec<usize>,_>>()
+                        .map_err(|e| error(n,e.to_string()))?;
+                    if v.len() < 3 || v.len().is_multiple_of(2){
+                        return Err(error(n,format!("invalid cage {:?}",value)));
+                    }
+                    let cells = v[1..].chunks(2).map(|c| (c[0],c[1])).collect();
+                    cages.push((n,Cage::new(v[0] as u32,cells)));
+                },
+                "regions" => regions = Some((n,value.parse::<Regions>()
+                    .map_err(|e| error(n,e))?)),
+                "selected" => {
+                    let v = value.split_whitespace()
+                        .map(|x| x.parse::<usize>())
+                        .collect::<Result<Vec<usize>,_>>()
+                        .map_err(|e| error(n,e.to_string()))?;
+                    match v[..]{
+                        [i,j] => selected_cell = Some((n,(i,j))),
+                        _ => return Err(error(n,format!("invalid cell {:?}",value))),
+                    }
+                },
+                "seed" => seed = Some(value.parse::<u64>()
+                    .map_err(|e| error(n,e.to_string()))?),
+                "time" => time = Duration::from_millis(value.parse::<u64>()
+                    .map_err(|e| error(n,e.to_string()))?),
+                "mistakes" => mistakes.mistakes = value.parse::<u32>()
+                    .map_err(|e| error(n,e.to_string()))?,
+                "check" => mistakes.mode = CheckMode::from_name(value)
+                    .ok_or_else(|| error(n,format!("unknown check mode {:?}",value)))?,
+                "max-mistakes" => mistakes.max_mistakes = Some(value.parse::<u32>()
+                    .map_err(|e| error(n,e.to_string()))?),
+                "mistake" => {
+                    let v = value.split_whitespace()
+                        .map(|x| x.parse::<usize>())
+                        .collect::<Result<Vec<usize>,_>>()
+                        .map_err(|e| error(n,e.to_string()))?;
+                    match v[..]{
+                        [i,j,d] => marked.push((n,(i,j),d)),
+                        _ => return Err(error(n,format!("invalid mistake {:?}",value))),
+                    }
+                },
+                "notes" => {
+                    let v = value.split_whitespace()
+                        .map(|x| x.parse::<u32>())
+                        .collect::<Result<Vec<u32>,_>>()
+                        .map_err(|e| error(n,e.to_string()))?;
+                    match v[..]{
+                        [i,j,corner,centre] => notes.push((n,(i as usize,j as usize),corner,centre)),
+                        _ => return Err(error(n,format!("invalid notes {:?}",value))),
+                    }
+                },
+                // written by a newer version
+                _ => {},
+            }
+        }
+
+        let mut gameboard = givens.ok_or_else(|| missing("givens"))?;
+        let shape = gameboard.shape();
+        let inside = |(i,j): (usize,usize)| i < shape.size() && j < shape.size();
+        if let Some(names) = rules{
+            if regions.is_none() && names.iter().any(|&(name,_)| name == "regions"){
+                return Err(missing("regions"));
+            }
+            let killer = names.iter().any(|&(name,_)| name == "cages");
+            gameboard.set_rules(names.into_iter().filter_map(|(_,rule)| rule).collect());
+            if killer{
+                gameboard.set_cages(vec![]);
+            }
+        }
+        if let Some((n,regions)) = regions{
+            if regions.size() != shape.size(){
+                return Err(error(n,"regions and givens differ in size".to_string()));
+            }
+            gameboard.set_regions(regions);
+        }
+        if !cages.is_empty(){
+            if let Some((n,_)) = cages.iter().find(|(_,cage)| !cage.cells.iter().all(|&ind| inside(ind))){
+                return Err(error(*n,"cage outside the board".to_string()));
+            }
+            gameboard.set_cages(cages.into_iter().map(|(_,cage)| cage).collect());
+        }
+        if let Some((n,cells)) = cells{
+            if cells.shape() != shape{
+                return Err(error(n,"cells and givens differ in size".to_string()));
+            }
+            for ind in shape.cells(){
+                if gameboard.get_readonly(ind){
+                    if cells.get(ind) != gameboard.get(ind){
+                        return Err(error(n,format!("cell {:?} differs from its given",ind)));
+                    }
+                }else{
+                    gameboard.set(ind,cells.get(ind));
+                }
+            }
+        }
+        gameboard.set_seed(seed);
+        let selected_cell = match selected_cell{
+            Some((n,ind)) if !inside(ind) => return Err(error(n,format!("invalid cell {:?}",ind))),
+            selected => selected.map(|(_,ind)| ind),
+        };
+        for (n,ind,corner,centre) in notes{
+            if !inside(ind) || (corner | centre) & !shape.all_digits() != 0{
+                return Err(error(n,format!("invalid notes {:?}",ind)));
+            }
+            gameboard.set_notes(ind,NoteKind::Corner,corner);
+            gameboard.set_notes(ind,NoteKind::Centre,centre);
+        }
+        let mut entries = vec![];
+        for (n,ind,d) in marked{
+            if !inside(ind) || d == 0 || d > shape.size(){
+                return Err(error(n,format!("invalid mistake {:?}",(ind,d))));
+            }
+            entries.push((ind,d as u8));
+        }
+        mistakes.set_marked(entries);
+
+        Ok(SaveGame{
+            gameboard,
+            selected_cell,
+            mistakes,
+            time,
+        })
+    }
+}
+
+#[test]
+fn test_save_roundtrip(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    gb.set((0,0),4);
+    gb.set_seed(Some(42));
+    gb.toggle_note((0,1),NoteKind::Corner,8);
+
+    let mut save = SaveGame::new(gb,Some((0,1)));
+    save.mistakes.mistakes = 2;
+    save.time = Duration::from_millis(61500);
+    let text = save.to_string();
+    let save : SaveGame = text.parse().unwrap();
+
+    assert_eq!(save.selected_cell,Some((0,1)));
+    assert_eq!(save.mistakes.mistakes,2);
+    assert_eq!(save.time,Duration::from_millis(61500));
+    assert_eq!(save.gameboard.get((0,0)),4);
+    assert!(!save.gameboard.get_readonly((0,0)));
+    assert!(save.gameboard.get_readonly((0,2)));
+    assert_eq!(save.gameboard.seed(),Some(42));
+    assert_eq!(save.gameboard.notes((0,1),NoteKind::Corner),1 << 8);
+}
+
+#[test]
+fn test_load_errors(){
+    assert!("hello".parse::<SaveGame>().is_err());
+    assert!("sudoku-save 99\n".parse::<SaveGame>().is_err());
+    assert!("sudoku-save 1\ncells 123\n".parse::<SaveGame>().is_err());
+
+    // whole-file errors have no line, the others the line of the culprit
+    let err = "sudoku-save 1\ntime 5\n".parse::<SaveGame>().err().unwrap();
+    assert!(matches!(err,LoadError::Format{line : None,..}));
+    assert_eq!(err.to_string(),"missing givens");
+    let text = "sudoku-save 1\ngivens 1..4.3.....2..1.\ncells 2..4.3.....2..1.\n";
+    let err = text.parse::<SaveGame>().err().unwrap();
+    assert_eq!(err.to_string(),"line 3: cell (0, 0) differs from its given");
+
+    // unknown keys are ignored
+    let text = format!("sudoku-save 1\ngivens {}\ntimer 12\n",".".repeat(SIZE*SIZE));
+    assert!(text.parse::<SaveGame>().is_ok());
+}
+
+#[test]
+fn test_save_shape(){
+    let mut gb : Gameboard = "1..4.3.....2..1.".parse().unwrap();
+    gb.toggle_note((0,1),NoteKind::Centre,2);
+    let save : SaveGame = SaveGame::new(gb,Some((3,3))).to_string().parse().unwrap();
+
+    assert_eq!(save.gameboard.size(),4);
+    assert_eq!(save.gameboard.notes((0,1),NoteKind::Centre),1 << 2);
+
+    let text = "sudoku-save 1\ngivens 1..4.3.....2..1.\nselected 4 0\n";
+    assert!(text.parse::<SaveGame>().is_err());
+    let text = "sudoku-save 1\ngivens 1..4.3.....2..1.\nnotes 0 1 32 0\n";
+    assert!(text.parse::<SaveGame>().is_err());
+    let text = "sudoku-save 1\ngivens 1..4.3.....2..1.\nnotes 0 1 4294967298 0\n";
+    assert!(text.parse::<SaveGame>().is_err());
+}
+
+#[test]
+fn test_save_rules(){
+    let mut gb = Gameboard::new();
+    gb.set_rules(constraint::sudoku_x());
+    let text = SaveGame::new(gb,None).to_string();
+    assert!(text.contains("rules rows columns boxes diagonals\n"));
+
+    let save : SaveGame = text.parse().unwrap();
+    assert!(save.gameboard.has_rule("diagonals"));
+    assert!(!SaveGame::new(Gameboard::new(),None).to_string().contains("rules"));
+
+    let text = format!("sudoku-save 1\ngivens {}\nrules rows thermo\n",".".repeat(SIZE*SIZE));
+    assert!(text.parse::<SaveGame>().is_err());
+}
+
+#[test]
+fn test_save_cages(){
+    let mut gb = Gameboard::new();
+    gb.set_cages(vec![Cage::new(3,vec![(0,0),(0,1)]),Cage::new(9,vec![(8,8)])]);
+    gb.set((0,0),2);
+    let text = SaveGame::new(gb.clone(),None).to_string();
+    assert!(text.contains("rules rows columns boxes cages\ncage 3 0 0 0 1\ncage 9 8 8\n"));
+
+    let save : SaveGame = text.parse().unwrap();
+    assert_eq!(save.gameboard.cages(),gb.cages());
+    assert_eq!(save.gameboard.get((0,0)),2);
+
+    let text = format!("sudoku-save 1\ngivens {}\ncage 3 0 0 9 1\n",".".repeat(SIZE*SIZE));
+    assert!(text.parse::<SaveGame>().is_err());
+}
+
+#[test]
+fn test_save_regions(){
+    let regions : Regions = "1122\n1122\n3344\n3344".parse().unwrap();
+    let mut gb = Gameboard::with_shape(crate::shape::Shape::new(2,2).unwrap());
+    gb.set_regions(regions.clone());
+    let text = SaveGame::new(gb.clone(),None).to_string();
+    assert!(text.contains("rules rows columns regions\nregions 1122112233443344\n"));
+
+    let save : SaveGame = text.parse().unwrap();
+    assert_eq!(save.gameboard.regions(),Some(&regions));
+    assert!(save.gameboard.has_rule("regions") && !save.gameboard.has_rule("boxes"));
+
+    assert!(text.replace("regions 1122112233443344\n","").parse::<SaveGame>().is_err());
+    assert!(text.replace("1122112233443344","1122112233443334").parse::<SaveGame>().is_err());
+}
+
+#[test]
+fn test_save_mistakes(){
+    let mut gb : Gameboard =
+        "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3.."
+        .parse().unwrap();
+    gb.cache_solution();
+    // the answer of (0,0) is 4
+    gb.set((0,0),5);
+
+    let mut save = SaveGame::new(gb,None);
+    save.mistakes = MistakeTracker::new(CheckMode::OnDemand,Some(3));
+    assert_eq!(save.mistakes.check(&save.gameboard),1);
+    let text = save.to_string();
+    assert!(text.contains("mistakes 1\ncheck on-demand\nmax-mistakes 3\nmistake 0 0 5\n"));
+
+    let mut save : SaveGame = text.parse().unwrap();
+    save.gameboard.cache_solution();
+    assert_eq!(save.mistakes.mode,CheckMode::OnDemand);
+    assert_eq!(save.mistakes.max_mistakes,Some(3));
+    assert!(save.mistakes.is_marked(&save.gameboard,(0,0)));
+    // the reported entry isn't counted again
+    assert_eq!(save.mistakes.check(&save.gameboard),0);
+    assert_eq!(save.mistakes.mistakes,1);
+
+    assert!(text.replace("mistake 0 0 5","mistake 0 0 10").parse::<SaveGame>().is_err());
+    assert!(text.replace("on-demand","sometimes").parse::<SaveGame>().is_err());
+}
@@ -0,0 +1,207 @@
+//! Board geometry
+//!
+//! A board of `size` rows and columns is split into `size` boxes of
+//! `box_width` x `box_height` cells, where `size = box_width * box_height`.
+//! The classic board has 3x3 boxes; 4x4 (2x2), 6x6 (3x2), 12x12 (4x3),
+//! 16x16 (4x4) and 25x25 (5x5) boards work the same way.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Cell location `(row, column)`.
+pub type Pos = (usize,usize);
+
+/// Digits of every cell, indexed by row and column. `0` is an empty cell.
+pub type Grid = Vec<Vec<u8>>;
+
+/// Largest supported size (digits must fit in a `u32` bit mask)
+pub const MAX_SIZE : usize = 25;
+
+/// Symbols of the digits `1..=MAX_SIZE`: `1`-`9`, then letters
+const SYMBOLS : &[u8] = b"123456789ABCDEFGHIJKLMNOP";
+
+/// Size of the boxes of a board
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct Shape{
+    /// Columns of a box
+    pub box_width : usize,
+    /// Rows of a box
+    pub box_height : usize,
+}
+
+impl Default for Shape{
+    fn default() -> Shape{
+        Shape::CLASSIC
+    }
+}
+
+impl Shape{
+    /// The classic 9x9 board with 3x3 boxes
+    pub const CLASSIC : Shape = Shape{box_width : 3,box_height : 3};
+
+    /// Create new Shape. Returns `None` if the board would be larger than `MAX_SIZE`.
+    pub fn new(box_width: usize,box_height: usize) -> Option<Shape>{
+        let size = box_width * box_height;
+
+        if size == 0 || size > MAX_SIZE{
+            None
+        }else{
+            Some(Shape{box_width,box_height})
+        }
+    }
+
+    /// Shape of a board with `size` rows, with boxes as square as possible
+    /// and at least as wide as high. Returns `None` if `size` is prime
+    /// (except 1) or larger than `MAX_SIZE`.
+    pub fn from_size(size: usize) -> Option<Shape>{
+        let height = (1..=size).take_while(|h| h*h <= size)
+            .filter(|&h| size.is_multiple_of(h))
+            .last()?;
+
+        if height == 1 && size > 1{
+            return None;
+        }
+
+        Shape::new(size/height,height)
+    }
+
+    /// Rows (columns, boxes, digits) of the board
+    pub fn size(self) -> usize{
+        self.box_width * self.box_height
+    }
+
+    /// Bit mask with a bit for every digit `1..=size`
+    pub fn all_digits(self) -> u32{
+        ((1 << self.size()) - 1) << 1
+    }
+
+    /// Digits `1..=size`
+    pub fn digits(self) -> impl Iterator<Item=u8>{
+        1..=self.size() as u8
+    }
+
+    /// All cell locations in row-major order
+    pub fn cells(self) -> impl Iterator<Item=Pos>{
+        let n = self.size();
+
+        (0..n*n).map(move |k| (k/n,k%n))
+    }
+
+    /// Cells of row `i`
+    pub fn row_cells(self,i: usize) -> Vec<Pos>{
+        (0..self.size()).map(|j| (i,j)).collect()
+    }
+
+    /// Cells of column `j`
+    pub fn col_cells(self,j: usize) -> Vec<Pos>{
+        (0..self.size()).map(|i| (i,j)).collect()
+    }
+
+    /// Cells of box `k`, boxes numbered in row-major order
+    pub fn box_cells(self,k: usize) -> Vec<Pos>{
+        let (w,h) = (self.box_width,self.box_height);
+        let top = (k / self.box_height) * h;
+        let left = (k % self.box_height) * w;
+
+        (0..self.size()).map(|n| (top + n/w,left + n%w)).collect()
+    }
+
+    /// Box index of a cell
+    pub fn box_of(self,p: Pos) -> usize{
+        (p.0 / self.box_height) * self.box_height + p.1 / self.box_width
+    }
+
+    /// All rows, columns and boxes
+    pub fn units(self) -> Vec<Vec<Pos>>{
+        let n = self.size();
+
+        (0..n).map(|i| self.row_cells(i))
+            .chain((0..n).map(|j| self.col_cells(j)))
+            .chain((0..n).map(|k| self.box_cells(k)))
+            .collect()
+    }
+
+    /// if two different cells share a row, column or box, returns true
+    pub fn sees(self,a: Pos,b: Pos) -> bool{
+        a != b && (a.0 == b.0 || a.1 == b.1 || self.box_of(a) == self.box_of(b))
+    }
+
+    /// Cells which share a unit with `p`
+    pub fn peers(self,p: Pos) -> Vec<Pos>{
+        self.cells().filter(|&q| self.sees(p,q)).collect()
+    }
+
+    /// Grid of empty cells
+    pub fn empty_grid(self) -> Grid{
+        vec![vec![0;self.size()];self.size()]
+    }
+}
+
+impl fmt::Display for Shape{
+    /// Write the box size as `WxH`
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f,"{}x{}",self.box_width,self.box_height)
+    }
+}
+
+impl FromStr for Shape{
+    type Err = String;
+
+    /// Parse a box size `WxH` or a board size such as `6`
+    fn from_str(s: &str) -> Result<Shape,String>{
+        let shape = match s.find('x'){
+            Some(k) => {
+                let w = s[..k].trim().parse::<usize>().map_err(|e| e.to_string())?;
+                let h = s[k+1..].trim().parse::<usize>().map_err(|e| e.to_string())?;
+                Shape::new(w,h)
+            },
+            None => Shape::from_size(s.trim().parse::<usize>().map_err(|e| e.to_string())?),
+        };
+
+        shape.ok_or_else(|| format!("unsupported board size {:?}",s))
+    }
+}
+
+/// Symbol of digit `d` (`1`-`9`, then `A` for 10 and so on)
+pub fn symbol(d: u8) -> Option<char>{
+    match d{
+        0 => None,
+        d => SYMBOLS.get(d as usize - 1).map(|&c| c as char),
+    }
+}
+
+/// Digit of a symbol, ignoring case. Returns `None` for other characters.
+pub fn digit(ch: char) -> Option<u8>{
+    let ch = ch.to_ascii_uppercase() as u32;
+
+    SYMBOLS.iter()
+        .position(|&c| c as u32 == ch)
+        .map(|n| n as u8 + 1)
+}
+
+#[test]
+fn test_shape_boxes(){
+    let shape = Shape::from_size(6).unwrap();
+    assert_eq!((shape.box_width,shape.box_height),(3,2));
+
+    // box 3 is the right box of the second band
+    assert_eq!(shape.box_cells(3),vec![(2,3),(2,4),(2,5),(3,3),(3,4),(3,5)]);
+    assert!(shape.box_cells(3).iter().all(|&p| shape.box_of(p) == 3));
+    assert_eq!(shape.peers((0,0)).len(),5 + 5 + 5 - 2 - 1);
+
+    assert_eq!(Shape::from_size(9),Some(Shape::CLASSIC));
+    assert_eq!(Shape::from_size(12),Some(Shape{box_width : 4,box_height : 3}));
+    assert_eq!(Shape::from_size(7),None);
+    assert_eq!(Shape::from_size(36),None);
+    assert_eq!("4x2".parse::<Shape>().map(Shape::size),Ok(8));
+}
+
+#[test]
+fn test_symbols(){
+    assert_eq!(symbol(9),Some('9'));
+    assert_eq!(symbol(10),Some('A'));
+    assert_eq!(symbol(25),Some('P'));
+    assert_eq!(symbol(0),None);
+    assert_eq!(digit('g'),Some(16));
+    assert_eq!(digit('.'),None);
+}
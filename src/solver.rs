@@ -1,56 +1,669 @@
 //! A program of Sudoku solver
 
 use rand::prelude::*;
+use rand_chacha::ChaChaRng;
+
+use crate::constraint::{self,Rule};
+use crate::gameboard::Gameboard;
+use crate::shape::{Grid,Shape,MAX_SIZE};
+#[cfg(test)]
+use crate::gameboard::SIZE;
+
+/// Nodes of the shortest restart when looking for a first answer
+const RESTART_NODES : usize = 100;
+
+/// Number of answers of a quiz
+#[derive(Clone,Debug,PartialEq)]
+pub enum SolutionCount{
+    /// The quiz has no answer.
+    None,
+    /// The quiz has exactly one answer.
+    Unique(Grid),
+    /// The quiz has at least `n` answers. Holds the answers found.
+    Multiple(usize,Vec<Grid>),
+}
+
+impl SolutionCount{
+    /// if the quiz has exactly one answer then true
+    pub fn is_unique(&self) -> bool{
+        matches!(self,SolutionCount::Unique(_))
+    }
+}
+
+/// Common interface of Sudoku solver backends.
+pub trait SudokuSolver{
+    /// Call `f` for every answer of sudoku until it returns `false`.
+    /// Returns the number of answers passed to `f`.
+    fn for_each_answer(&mut self,gb: &Gameboard,f: &mut dyn FnMut(&Grid) -> bool) -> usize;
+
+    /// Restart the random number generator from `seed`.
+    /// The same seed always yields the same answers.
+    fn reseed(&mut self,seed: u64);
+
+    /// Search all answers of sudoku(up to n_answer answers).
+    fn make_answer_list(&mut self, gb: &Gameboard,n_answer: usize) -> Vec<Grid>{
+        let mut v = vec![];
+
+        if n_answer > 0{
+            self.for_each_answer(gb,&mut |answer|{
+                v.push(answer.clone());
+                v.len() < n_answer
+            });
+        }
+
+        v
+    }
+
+    /// Count answers, stopping at `cap` (at least 2) answers.
+    fn count_solutions(&mut self,gb: &Gameboard,cap: usize) -> SolutionCount{
+        let mut answers = self.make_answer_list(gb,cap.max(2));
+
+        match answers.len(){
+            0 => SolutionCount::None,
+            1 => SolutionCount::Unique(answers.remove(0)),
+            n => SolutionCount::Multiple(n,answers),
+        }
+    }
+}
 
 /// Sudoku solver
+///
+/// Keeps a candidate bit mask per cell, propagates naked singles after every
+/// placement, places hidden singles, removes candidates locked in the
+/// overlap of two units and lets the rules of the board prune candidates
+/// before branching. Branches on the cell with the fewest candidates, or on
+/// the places of a digit in a unit if there are fewer of them.
 pub struct Solver{
+    /// Shape the peers and units were made for
+    shape : Shape,
+    /// Rules of the board being solved
+    rules : Vec<Rule>,
+    /// Peers (cells sharing a unit) of every cell.
+    peers : Vec<Vec<usize>>,
+    /// Cells of every unit of the rules.
+    units : Vec<Vec<usize>>,
+    /// Overlaps of two units which hold every digit: the cells of the
+    /// first unit only, of both, and of the second unit only.
+    overlaps : Vec<(Vec<usize>,Vec<usize>,Vec<usize>)>,
+    /// Decides the order in which digits are tried.
+    rng : ChaChaRng,
+    /// Nodes the search may still visit, `None` for no limit
+    budget : Option<usize>,
+}
+
+/// Search state of the solver.
+#[derive(Clone)]
+struct State{
+    /// Digit of every cell in row-major order. `0` is an empty cell.
+    cells : Vec<u8>,
+    /// Candidate bit mask of every cell. Bit `d` is set if digit `d` is possible.
+    candidates : Vec<u32>,
 }
 
-use crate::gameboard::{SIZE,Gameboard};
+impl Default for Solver{
+    fn default() -> Solver{
+        Solver::new()
+    }
+}
 
 impl Solver{
     /// Create new Solver
     pub fn new()->Solver{
+        Solver::with_rng(ChaChaRng::from_entropy())
+    }
+
+    /// Create new Solver whose answers are reproducible from `seed`
+    pub fn from_seed(seed: u64)->Solver{
+        Solver::with_rng(ChaChaRng::seed_from_u64(seed))
+    }
+
+    /// Create new Solver seeded by another random number generator
+    pub fn from_rng<R: RngCore>(rng: &mut R)->Solver{
+        Solver::with_rng(ChaChaRng::from_seed(rng.gen()))
+    }
+
+    /// Create new Solver for classic boards drawing from `rng`
+    fn with_rng(rng: ChaChaRng)->Solver{
+        let rules = constraint::classic();
+        let units = make_units(Shape::CLASSIC,&rules);
+
         Solver{
+            shape : Shape::CLASSIC,
+            rules,
+            peers : make_peers(Shape::CLASSIC,&units),
+            overlaps : make_overlaps(Shape::CLASSIC,&units),
+            units,
+            rng,
+            budget : None,
         }
     }
 
-    /// Search all answers of sudoku(up to n_aswer answers).
-    pub fn make_answer_list(&mut self, gb: &mut Gameboard,n_answer: usize) -> Vec<[[u8;SIZE]; SIZE]>{
-        let mut v = vec![];
-        self.solve_core(gb,n_answer,0,&mut v);
+    /// Build the search state of a gameboard.
+    /// Returns `None` if the givens already contradict each other.
+    fn initial_state(&mut self,gb: &Gameboard) -> Option<State>{
+        let units = make_units(gb.shape(),gb.rules());
+        if gb.shape() != self.shape || units != self.units{
+            self.shape = gb.shape();
+            self.peers = make_peers(self.shape,&units);
+            self.overlaps = make_overlaps(self.shape,&units);
+            self.units = units;
+        }
+        self.rules = gb.rules().to_vec();
 
-        v
+        let n = self.shape.size();
+        let mut state = State{
+            cells : vec![0;n*n],
+            candidates : vec![self.shape.all_digits();n*n],
+        };
+
+        for (pos,ind) in self.shape.cells().enumerate(){
+            let d = gb.get(ind);
+            if d != 0 && !self.assign(&mut state,pos,d){
+                return None;
+            }
+        }
+
+        Some(state)
+    }
+
+    /// Place digit `d` at `pos` and propagate naked singles.
+    /// Returns `false` on contradiction.
+    fn assign(&self,state: &mut State,pos: usize,d: u8) -> bool{
+        let mut queue = vec![(pos,d)];
+
+        while let Some((pos,d)) = queue.pop(){
+            if state.cells[pos] == d{
+                continue;
+            }
+            if state.cells[pos] != 0 || state.candidates[pos] & (1 << d) == 0{
+                return false;
+            }
+
+            state.cells[pos] = d;
+            state.candidates[pos] = 1 << d;
+
+            for &p in self.peers[pos].iter(){
+                if state.candidates[p] & (1 << d) == 0{
+                    continue;
+                }
+                if state.cells[p] != 0{
+                    // peer already holds the same digit
+                    return false;
+                }
+
+                state.candidates[p] &= !(1 << d);
+                match state.candidates[p].count_ones(){
+                    0 => return false,
+                    1 => queue.push((p,state.candidates[p].trailing_zeros() as u8)),
+                    _ => {},
+                }
+            }
+        }
+
+        true
     }
 
-    /// Core part of Sudoku solver
-    fn solve_core(&mut self,gb: &mut Gameboard,n_answer: usize, position : usize, answers : &mut Vec<[[u8;SIZE];SIZE]> ){
-        let (i,j) = (position/SIZE,position%SIZE);
+    /// Place every digit which has only one place left in a unit, until
+    /// there is none. Returns `false` on contradiction.
+    fn hidden_singles(&self,state: &mut State) -> bool{
+        loop{
+            let mut changed = false;
+
+            // only units of `size` cells must hold every digit
+            for unit in self.units.iter().filter(|unit| unit.len() == self.shape.size()){
+                // digits with at least one and at least two places
+                let (mut once,mut twice) = (0,0);
+                for &p in unit.iter(){
+                    twice |= once & state.candidates[p];
+                    once |= state.candidates[p];
+                }
+                if once != self.shape.all_digits(){
+                    return false;
+                }
+
+                let singles = once & !twice;
+                for &p in unit.iter(){
+                    let d = state.candidates[p] & singles;
+                    if d == 0 || state.cells[p] != 0{
+                        continue;
+                    }
+                    if d.count_ones() > 1 || !self.assign(state,p,d.trailing_zeros() as u8){
+                        return false;
+                    }
+                    changed = true;
+                }
+            }
+
+            if !changed{
+                return true;
+            }
+        }
+    }
+
+    /// Let every rule remove candidates and place the naked singles this
+    /// leaves. Returns `false` on contradiction.
+    fn prune(&self,state: &mut State) -> bool{
+        let before = state.candidates.clone();
+        for rule in self.rules.iter(){
+            if !rule.prune(self.shape,&mut state.candidates){
+                return false;
+            }
+        }
+
+        for (p,&mask) in before.iter().enumerate(){
+            if state.candidates[p] == mask{
+                continue;
+            }
+            match state.candidates[p].count_ones(){
+                0 => return false,
+                1 if state.cells[p] == 0 => {
+                    let d = state.candidates[p].trailing_zeros() as u8;
+                    if !self.assign(state,p,d){
+                        return false;
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        true
+    }
+
+    /// Remove the digits which must be in the overlap of two units from
+    /// the rest of the second unit. Returns `false` on contradiction.
+    fn intersections(&self,state: &mut State) -> bool{
+        for (first,both,second) in self.overlaps.iter(){
+            let inside = both.iter().fold(0,|m,&p| m | state.candidates[p]);
+            let outside = first.iter().fold(0,|m,&p| m | state.candidates[p]);
+            let locked = inside & !outside;
+            if locked == 0{
+                continue;
+            }
+
+            for &p in second.iter(){
+                if state.candidates[p] & locked == 0{
+                    continue;
+                }
+                if state.cells[p] != 0{
+                    return false;
+                }
+                state.candidates[p] &= !locked;
+                match state.candidates[p].count_ones(){
+                    0 => return false,
+                    1 => {
+                        let d = state.candidates[p].trailing_zeros() as u8;
+                        if !self.assign(state,p,d){
+                            return false;
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Apply hidden singles and the rules until nothing changes.
+    /// Returns `false` on contradiction.
+    fn propagate(&self,state: &mut State) -> bool{
+        loop{
+            if !self.hidden_singles(state){
+                return false;
+            }
+            if !self.intersections(state){
+                return false;
+            }
+
+            let before = state.candidates.clone();
+            if !self.prune(state){
+                return false;
+            }
+            if state.candidates == before{
+                return true;
+            }
+        }
+    }
+
+    /// A unit holding every digit and a digit with the fewest places (but
+    /// at least two) left in it, and the number of places
+    fn fewest_places(&self,state: &State) -> Option<(&[usize],u8,usize)>{
+        let mut best : Option<(&[usize],u8,usize)> = None;
+
+        for unit in self.units.iter().filter(|unit| unit.len() == self.shape.size()){
+            let mut places = [0;MAX_SIZE+1];
+            for &p in unit.iter(){
+                let mut mask = state.candidates[p];
+                while mask != 0{
+                    places[mask.trailing_zeros() as usize] += 1;
+                    mask &= mask - 1;
+                }
+            }
+
+            for d in self.shape.digits(){
+                let k = places[d as usize];
+                if k >= 2 && best.is_none_or(|(_,_,m)| k < m){
+                    best = Some((unit,d,k));
+                }
+            }
+            if best.is_some_and(|(_,_,m)| m == 2){
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// Core part of Sudoku solver.
+    /// Returns `false` if `f` asked to stop.
+    fn solve_core(&mut self,mut state: State,f: &mut dyn FnMut(&Grid) -> bool) -> bool{
+        match self.budget{
+            Some(0) => return false,
+            Some(ref mut budget) => *budget -= 1,
+            None => {},
+        }
+        if !self.propagate(&mut state){
+            return true;
+        }
+
+        // minimum remaining values: pick the empty cell with fewest candidates
+        let target = (0..state.cells.len())
+            .filter(|&p| state.cells[p] == 0)
+            .min_by_key(|&p| state.candidates[p].count_ones());
+
+        let pos = match target{
+            Some(pos) => pos,
+            None => {
+                // finished; rules without units or pruning are checked here
+                let cells = to_cells(self.shape,&state);
+                if !self.rules.iter().all(|rule| rule.validate(self.shape,&cells)){
+                    return true;
+                }
+                return f(&cells);
+            },
+        };
+
+        let mut choices = self.shape.digits()
+            .filter(|d| state.candidates[pos] & (1 << d) != 0)
+            .map(|d| (pos,d))
+            .collect::<Vec<(usize,u8)>>();
+        // the places of a digit in a unit may be a smaller branch
+        if choices.len() > 2{
+            if let Some((unit,d,_)) = self.fewest_places(&state).filter(|&(_,_,k)| k < choices.len()){
+                choices = unit.iter()
+                    .filter(|&&p| state.candidates[p] & (1 << d) != 0)
+                    .map(|&p| (p,d))
+                    .collect();
+            }
+        }
+        shuffle(&mut self.rng,&mut choices);
+
+        for (pos,d) in choices{
+            let mut next = state.clone();
+            if self.assign(&mut next,pos,d) && !self.solve_core(next,f){
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl SudokuSolver for Solver{
+    fn for_each_answer(&mut self,gb: &Gameboard,f: &mut dyn FnMut(&Grid) -> bool) -> usize{
+        let mut n = 0;
 
         if gb.invalid(){
-            return;
-        }
-
-        if position == SIZE * SIZE{
-            // valid and finished
-            answers.push(gb.copy_cells());
-        }else if gb.get((i,j))!= 0{
-            // already filled value
-            self.solve_core(gb, n_answer,position+1,answers);
-        }else{
-            // brute force method
-            let mut vec = (1..SIZE+1).collect::<Vec<usize>>();
-            vec.shuffle(&mut rand::thread_rng());
-
-            for d in vec{
-                if n_answer <= answers.len(){
+            return n;
+        }
+
+        let state = match self.initial_state(gb){
+            Some(state) => state,
+            None => return n,
+        };
+
+        // An early wrong guess on a sparse board (e.g. an empty Jigsaw
+        // board) may take ages to refute. Short restarts, now and then a
+        // longer one, find a first answer; the complete search skips it.
+        let mut first = None;
+        for restart in 1..{
+            self.budget = Some(RESTART_NODES * luby(restart));
+            self.solve_core(state.clone(),&mut |answer|{
+                first = Some(answer.clone());
+                false
+            });
+            let exhausted = self.budget == Some(0);
+            self.budget = None;
+
+            match first{
+                Some(ref answer) => {
+                    n += 1;
+                    if !f(answer){
+                        return n;
+                    }
                     break;
-                }
+                },
+                // the search was complete
+                None if !exhausted => return n,
+                None => {},
+            }
+        }
+
+        self.solve_core(state,&mut |answer|{
+            if first.as_ref() == Some(answer){
+                return true;
+            }
+            n += 1;
+            f(answer)
+        });
+
+        n
+    }
+
+    fn reseed(&mut self,seed: u64){
+        self.rng = ChaChaRng::seed_from_u64(seed);
+    }
+}
+
+/// Shuffle a slice.
+///
+/// Unlike `SliceRandom::shuffle` this only draws `u32` values, so the result
+/// for a given seed doesn't depend on the pointer width of the platform.
+pub(crate) fn shuffle<T,R: Rng>(rng: &mut R,v: &mut [T]){
+    for i in (1..v.len()).rev(){
+        let j = rng.gen_range(0,i as u32 + 1) as usize;
+        v.swap(i,j);
+    }
+}
+
+/// Term `i` (from 1) of the Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, ...,
+/// which spreads the node limits of the restarts
+fn luby(mut i: usize) -> usize{
+    loop{
+        // 2^k - 1 is the first index of 2^(k-1)
+        let k = (usize::BITS - i.leading_zeros()) as usize;
+        if i == (1 << k) - 1{
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+/// Make the overlaps of every two units of `size` cells which share at
+/// least two cells.
+fn make_overlaps(shape: Shape,units: &[Vec<usize>]) -> Vec<(Vec<usize>,Vec<usize>,Vec<usize>)>{
+    let full = units.iter().filter(|unit| unit.len() == shape.size()).collect::<Vec<&Vec<usize>>>();
+    let mut overlaps = vec![];
+
+    for a in full.iter(){
+        for b in full.iter(){
+            let both = a.iter().cloned().filter(|p| b.contains(p)).collect::<Vec<usize>>();
+            if both.len() < 2 || both.len() == a.len(){
+                continue;
+            }
+            let first = a.iter().cloned().filter(|p| !both.contains(p)).collect();
+            let second = b.iter().cloned().filter(|p| !both.contains(p)).collect();
+            overlaps.push((first,both,second));
+        }
+    }
+
+    overlaps
+}
+
+/// Make the peer list of every cell.
+fn make_peers(shape: Shape,units: &[Vec<usize>]) -> Vec<Vec<usize>>{
+    let n = shape.size();
+    let mut seen = vec![vec![false;n*n];n*n];
 
-                gb.set((i,j),d as u8);
-                self.solve_core(gb,n_answer,position+1,answers);
+    for unit in units.iter(){
+        for &p in unit.iter(){
+            for &q in unit.iter().filter(|&&q| q != p){
+                seen[p][q] = true;
             }
-            gb.set((i,j),0);
         }
     }
-}
\ No newline at end of file
+
+    seen.iter()
+        .map(|row| (0..n*n).filter(|&q| row[q]).collect())
+        .collect()
+}
+
+/// Make the cell list of every unit of the rules.
+fn make_units(shape: Shape,rules: &[Rule]) -> Vec<Vec<usize>>{
+    let n = shape.size();
+
+    constraint::units(rules,shape).iter()
+        .map(|unit| unit.iter().map(|&(i,j)| i*n + j).collect())
+        .collect()
+}
+
+/// Convert a finished state into cells.
+fn to_cells(shape: Shape,state: &State) -> Grid{
+    state.cells.chunks(shape.size()).map(|row| row.to_vec()).collect()
+}
+
+#[cfg(test)]
+pub(crate) fn make_board(quiz: &str) -> Gameboard{
+    let mut gb = Gameboard::new();
+
+    for (n,c) in quiz.chars().enumerate(){
+        gb.set((n/SIZE,n%SIZE),c.to_digit(10).unwrap_or(0) as u8);
+    }
+
+    gb
+}
+
+#[test]
+fn test_solve_hard(){
+    let gb = make_board(
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......");
+    let answer = "417369825632158947958724316825437169791586432346912758289643571573291684164875293";
+
+    let answers = Solver::new().make_answer_list(&gb,2);
+
+    assert_eq!(answers.len(),1);
+    for (n,c) in answer.chars().enumerate(){
+        assert_eq!(answers[0][n/SIZE][n%SIZE],c.to_digit(10).unwrap() as u8);
+    }
+}
+
+#[test]
+fn test_solve_multiple(){
+    let gb = Gameboard::new();
+
+    assert_eq!(Solver::new().make_answer_list(&gb,5).len(),5);
+}
+
+#[test]
+fn test_solve_seed(){
+    let gb = Gameboard::new();
+    let a = Solver::from_seed(42).make_answer_list(&gb,3);
+    let b = Solver::from_seed(42).make_answer_list(&gb,3);
+
+    assert_eq!(a,b);
+}
+
+#[test]
+fn test_solve_contradiction(){
+    // (0,8) can't hold any digit
+    let gb = make_board(
+        "12345678.........9");
+
+    assert!(Solver::new().make_answer_list(&gb,1).is_empty());
+}
+
+#[test]
+fn test_count_solutions(){
+    let mut solver = Solver::new();
+
+    let gb = make_board(
+        "4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4......");
+    assert!(solver.count_solutions(&gb,10).is_unique());
+
+    match solver.count_solutions(&Gameboard::new(),3){
+        SolutionCount::Multiple(3,answers) => assert_eq!(answers.len(),3),
+        _ => panic!("expected multiple answers"),
+    }
+
+    let gb = make_board("12345678.........9");
+    assert_eq!(solver.count_solutions(&gb,10),SolutionCount::None);
+}
+
+#[test]
+fn test_for_each_answer(){
+    // a solved grid with the 4 cells of a deadly pattern removed has 2 answers
+    let mut gb = make_board(
+        "417369825632158947958724316825437169791586432346912758289643571573291684164875293");
+    for &ind in [(0,1),(0,3),(1,1),(1,3)].iter(){
+        gb.set(ind,0);
+    }
+
+    let mut answers = vec![];
+    let n = Solver::new().for_each_answer(&gb,&mut |answer|{
+        answers.push(answer.clone());
+        true
+    });
+
+    assert_eq!(n,2);
+    assert_ne!(answers[0],answers[1]);
+}
+
+#[test]
+fn test_solve_shapes(){
+    for &size in [4,6,8,12].iter(){
+        let gb = Gameboard::with_shape(Shape::from_size(size).unwrap());
+        let answers = Solver::from_seed(1).make_answer_list(&gb,2);
+
+        assert_eq!(answers.len(),2);
+        assert_eq!(answers[0].len(),size);
+
+        let mut solved = gb.clone();
+        for (i,j) in gb.shape().cells(){
+            solved.set((i,j),answers[0][i][j]);
+        }
+        assert!(solved.finished());
+    }
+}
+
+#[test]
+fn test_luby(){
+    let terms = (1..=15).map(luby).collect::<Vec<usize>>();
+    assert_eq!(terms,vec![1,1,2,1,1,2,4,1,1,2,1,1,2,4,8]);
+}
+
+#[test]
+fn test_restart_answers(){
+    use std::collections::HashSet;
+    use crate::dancing_links::DancingLinksSolver;
+
+    // the hard quiz without its given at (4,6) has 794 answers
+    let gb = make_board(
+        "4.....8.5.3..........7......2.....6.....8........1.......6.3.7.5..2.....1.4......");
+    let answers = Solver::from_seed(5).make_answer_list(&gb,usize::MAX);
+
+    // every answer exactly once, the same ones as the other backend
+    assert_eq!(answers.len(),794);
+    assert_eq!(answers.iter().collect::<HashSet<&Grid>>().len(),answers.len());
+    assert_eq!(DancingLinksSolver::new().make_answer_list(&gb,usize::MAX).len(),answers.len());
+    assert_eq!(Solver::from_seed(5).make_answer_list(&gb,usize::MAX),answers);
+}
@@ -0,0 +1,237 @@
+//! Statistics of finished games
+//!
+//! Statistics are kept for all games and for every difficulty level, and
+//! are stored in a text file with a header line and one line per record:
+//!
+//! ```text
+//! sudoku-stats 1
+//! all 12 10 183000 2544000 4 6
+//! Easy 5 5 183000 1021000 5 5
+//! ```
+//!
+//! The columns are games played, games won, best time and total time of
+//! the won games (in milliseconds, `-` for no best time), current streak
+//! and best streak.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::rating::Difficulty;
+use crate::save::LoadError;
+
+/// Header of statistics files
+const HEADER : &str = "sudoku-stats";
+
+/// Newest statistics file version
+pub const VERSION : u32 = 1;
+
+/// Statistics of a set of games
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct Record{
+    /// Games finished, won or lost
+    pub played : u32,
+    /// Games won
+    pub won : u32,
+    /// Fastest win
+    pub best : Option<Duration>,
+    /// Sum of the times of all wins
+    pub total : Duration,
+    /// Wins in a row up to the last game
+    pub streak : u32,
+    /// Longest streak so far
+    pub best_streak : u32,
+}
+
+impl Record{
+    /// Average time of a win
+    pub fn average(&self) -> Option<Duration>{
+        if self.won == 0{
+            None
+        }else{
+            Some(self.total / self.won)
+        }
+    }
+
+    /// Count a won game. Returns `true` if `time` is a new best time.
+    pub fn add_win(&mut self,time: Duration) -> bool{
+        let new_best = self.best.is_none_or(|best| time < best);
+
+        self.played += 1;
+        self.won += 1;
+        self.total += time;
+        self.streak += 1;
+        self.best_streak = self.best_streak.max(self.streak);
+        if new_best{
+            self.best = Some(time);
+        }
+
+        new_best
+    }
+
+    /// Count a lost or abandoned game
+    pub fn add_loss(&mut self){
+        self.played += 1;
+        self.streak = 0;
+    }
+}
+
+/// Statistics of all games and of every difficulty level
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct Stats{
+    /// All games
+    pub all : Record,
+    /// Games of every level, in the order of `Difficulty::ALL`
+    levels : [Record;5],
+}
+
+impl Stats{
+    /// Create new empty Stats
+    pub fn new() -> Stats{
+        Stats::default()
+    }
+
+    /// Statistics of one difficulty level
+    pub fn level(&self,d: Difficulty) -> &Record{
+        &self.levels[d as usize]
+    }
+
+    /// Count a won game. Returns `true` if `time` is a new best time of the level.
+    pub fn add_win(&mut self,d: Difficulty,time: Duration) -> bool{
+        self.all.add_win(time);
+        self.levels[d as usize].add_win(time)
+    }
+
+    /// Count a lost or abandoned game
+    pub fn add_loss(&mut self,d: Difficulty){
+        self.all.add_loss();
+        self.levels[d as usize].add_loss();
+    }
+
+    /// Write the statistics file
+    pub fn save<P: AsRef<Path>>(&self,path: P) -> io::Result<()>{
+        fs::write(path,self.to_string())
+    }
+
+    /// Read a statistics file. A missing file is empty statistics.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Stats,LoadError>{
+        match fs::read_to_string(path){
+            Ok(text) => text.parse(),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Stats::new()),
+            Err(e) => Err(LoadError::Io(e)),
+        }
+    }
+}
+
+/// Write one record line
+fn write_record(f: &mut fmt::Formatter,name: &str,r: &Record) -> fmt::Result{
+    let best = r.best.map_or("-".to_string(),|b| b.as_millis().to_string());
+
+    writeln!(f,"{} {} {} {} {} {} {}",
+        name,r.played,r.won,best,r.total.as_millis(),r.streak,r.best_streak)
+}
+
+/// Parse the columns of one record line
+fn parse_record(columns: &[&str]) -> Result<Record,String>{
+    let count = |s: &str| s.parse::<u32>().map_err(|e| format!("{:?}: {}",s,e));
+    let millis = |s: &str| s.parse::<u64>().map_err(|e| format!("{:?}: {}",s,e));
+
+    match columns{
+        [played,won,best,total,streak,best_streak] => Ok(Record{
+            played : count(played)?,
+            won : count(won)?,
+            best : match *best{
+                "-" => None,
+                b => Some(Duration::from_millis(millis(b)?)),
+            },
+            total : Duration::from_millis(millis(total)?),
+            streak : count(streak)?,
+            best_streak : count(best_streak)?,
+        }),
+        _ => Err(format!("expected 6 columns but found {}",columns.len())),
+    }
+}
+
+impl fmt::Display for Stats{
+    fn fmt(&self,f: &mut fmt::Formatter) -> fmt::Result{
+        writeln!(f,"{} {}",HEADER,VERSION)?;
+        write_record(f,"all",&self.all)?;
+        for &d in Difficulty::ALL.iter(){
+            write_record(f,d.name(),self.level(d))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Stats{
+    type Err = LoadError;
+
+    fn from_str(text: &str) -> Result<Stats,LoadError>{
+        let error = |line: usize,message: String| LoadError::Format{line : Some(line),message};
+        let mut lines = text.lines().enumerate().map(|(n,l)| (n+1,l.trim()));
+
+        // header
+        let version = lines.next()
+            .and_then(|(_,l)| l.strip_prefix(HEADER))
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| error(1,"not a statistics file".to_string()))?;
+        if version > VERSION{
+            return Err(error(1,format!("unsupported version {}",version)));
+        }
+
+        let mut stats = Stats::new();
+        for (n,l) in lines.filter(|(_,l)| !l.is_empty()){
+            let columns = l.split_whitespace().collect::<Vec<&str>>();
+            let record = parse_record(&columns[1..]).map_err(|e| error(n,e))?;
+
+            // unknown names are written by a newer version
+            if columns[0] == "all"{
+                stats.all = record;
+            }else if let Ok(d) = columns[0].parse::<Difficulty>(){
+                stats.levels[d as usize] = record;
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[test]
+fn test_stats_record(){
+    let mut stats = Stats::new();
+
+    assert!(stats.add_win(Difficulty::Easy,Duration::from_secs(200)));
+    assert!(stats.add_win(Difficulty::Easy,Duration::from_secs(100)));
+    assert!(!stats.add_win(Difficulty::Easy,Duration::from_secs(300)));
+    stats.add_loss(Difficulty::Hard);
+    assert!(stats.add_win(Difficulty::Hard,Duration::from_secs(900)));
+
+    let easy = stats.level(Difficulty::Easy);
+    assert_eq!((easy.played,easy.won,easy.streak),(3,3,3));
+    assert_eq!(easy.best,Some(Duration::from_secs(100)));
+    assert_eq!(easy.average(),Some(Duration::from_secs(200)));
+
+    assert_eq!((stats.all.played,stats.all.won),(5,4));
+    assert_eq!((stats.all.streak,stats.all.best_streak),(1,3));
+    assert_eq!(stats.level(Difficulty::Evil).average(),None);
+}
+
+#[test]
+fn test_stats_roundtrip(){
+    let mut stats = Stats::new();
+    stats.add_win(Difficulty::Medium,Duration::from_millis(123456));
+    stats.add_loss(Difficulty::Evil);
+
+    let parsed : Stats = stats.to_string().parse().unwrap();
+    assert_eq!(parsed,stats);
+
+    assert!("sudoku-stats 1\nall 1 2\n".parse::<Stats>().is_err());
+    assert!("sudoku-save 1\n".parse::<Stats>().is_err());
+    // counts beyond u32 are rejected instead of wrapping around
+    let err = "sudoku-stats 1\nall 4294967297 1 - 0 0 0\n".parse::<Stats>().err().unwrap();
+    assert_eq!(err.to_string(),"line 2: \"4294967297\": number too large to fit in target type");
+}
@@ -0,0 +1,104 @@
+//! Game timer which can be paused
+
+use std::time::{Duration,Instant};
+
+/// Measures the playing time of a game
+#[derive(Clone,Debug)]
+pub struct Timer{
+    /// Time measured before the last start
+    elapsed : Duration,
+    /// When the timer was last started, `None` while paused
+    started : Option<Instant>,
+}
+
+impl Default for Timer{
+    fn default() -> Timer{
+        Timer::new()
+    }
+}
+
+impl Timer{
+    /// Create new Timer running from zero
+    pub fn new() -> Timer{
+        Timer{
+            elapsed : Duration::from_secs(0),
+            started : Some(Instant::now()),
+        }
+    }
+
+    /// Create new paused Timer which has already measured `elapsed`
+    pub fn paused(elapsed: Duration) -> Timer{
+        Timer{
+            elapsed,
+            started : None,
+        }
+    }
+
+    /// Measured time
+    pub fn elapsed(&self) -> Duration{
+        match self.started{
+            Some(started) => self.elapsed + started.elapsed(),
+            None => self.elapsed,
+        }
+    }
+
+    /// if the timer is running then true
+    pub fn is_running(&self) -> bool{
+        self.started.is_some()
+    }
+
+    /// Stop measuring time
+    pub fn pause(&mut self){
+        self.elapsed = self.elapsed();
+        self.started = None;
+    }
+
+    /// Continue measuring time
+    pub fn resume(&mut self){
+        if self.started.is_none(){
+            self.started = Some(Instant::now());
+        }
+    }
+
+    /// Pause a running timer or resume a paused one
+    pub fn toggle(&mut self){
+        if self.is_running(){
+            self.pause();
+        }else{
+            self.resume();
+        }
+    }
+}
+
+/// Format a duration as `m:ss` or `h:mm:ss`
+pub fn format_duration(d: Duration) -> String{
+    let s = d.as_secs();
+
+    if s >= 3600{
+        format!("{}:{:02}:{:02}",s/3600,s/60%60,s%60)
+    }else{
+        format!("{}:{:02}",s/60,s%60)
+    }
+}
+
+#[test]
+fn test_timer_pause(){
+    let mut timer = Timer::paused(Duration::from_secs(5));
+    assert!(!timer.is_running());
+    assert_eq!(timer.elapsed(),Duration::from_secs(5));
+
+    timer.toggle();
+    assert!(timer.is_running());
+    assert!(timer.elapsed() >= Duration::from_secs(5));
+
+    timer.pause();
+    let paused = timer.elapsed();
+    assert_eq!(timer.elapsed(),paused);
+}
+
+#[test]
+fn test_format_duration(){
+    assert_eq!(format_duration(Duration::from_secs(65)),"1:05");
+    assert_eq!(format_duration(Duration::from_millis(999)),"0:00");
+    assert_eq!(format_duration(Duration::from_secs(3725)),"1:02:05");
+}